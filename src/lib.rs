@@ -1,12 +1,18 @@
+#![allow(non_upper_case_globals)]
+
 #[macro_use]
 extern crate bitflags;
 
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::collections::BTreeMap;
+use std::error;
 use std::f32;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
 
 /// Rectangle for padding and spacing constraints.
-#[derive(Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Rectangle {
     pub top: f32,
     pub left: f32,
@@ -14,8 +20,42 @@ pub struct Rectangle {
     pub right: f32,
 }
 
+impl Rectangle {
+    /// Creates a rectangle covering the area from `x`, `y` which is
+    /// `width` wide and `height` tall.
+    pub fn from_box(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Rectangle {
+            top: y,
+            left: x,
+            bottom: y + height,
+            right: x + width,
+        }
+    }
+
+    /// Returns the horizontal distance between the left and right edges.
+    pub fn width(&self) -> f32 {
+        self.right - self.left
+    }
+
+    /// Returns the vertical distance between the top and bottom edges.
+    pub fn height(&self) -> f32 {
+        self.bottom - self.top
+    }
+
+    /// Multiplies every side by a given factor. Used when converting
+    /// logical units into physical pixels.
+    pub fn scaled(&self, factor: f32) -> Self {
+        Rectangle {
+            top: self.top * factor,
+            left: self.left * factor,
+            bottom: self.bottom * factor,
+            right: self.right * factor,
+        }
+    }
+}
+
 /// Individual size constraint for a cell.
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Size {
     pub width: f32,
     pub height: f32,
@@ -53,14 +93,60 @@ impl Size {
         }
     }
 
+    /// Multiplies the width and height by a given factor. Used when
+    /// converting logical units into physical pixels.
+    pub fn scaled(&self, factor: f32) -> Self {
+        Size {
+            width: self.width * factor,
+            height: self.height * factor,
+        }
+    }
+
     /// Returns whether this size should fit within another size.
     pub fn within(&self, other: &Size) -> bool {
         other.width > self.width && other.height > self.height
     }
 }
 
+/// Unit in which the lengths given to a layout are measured.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Unit {
+    /// Physical pixels, used as given.
+    #[default]
+    Pixels,
+    /// Density independent pixels, multiplied by the table's scale.
+    Dp,
+    /// Multiples of the table's em size, which is itself given in
+    /// density independent pixels.
+    Em,
+}
+
+impl Unit {
+    /// Returns the number of physical pixels in one of this unit, for a
+    /// table with the given `scale` and `em` size.
+    fn pixels(self, scale: f32, em: f32) -> f32 {
+        match self {
+            Unit::Pixels => 1.0,
+            Unit::Dp => scale,
+            Unit::Em => em * scale,
+        }
+    }
+}
+
+/// Preferred length of a cell given relative to the table it is laid
+/// out in, rather than in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Relative {
+    /// Percentage of the table's width or height, once the table's
+    /// padding and the gaps between columns or rows are taken out.
+    Percent(f32),
+    /// Share of the space left over once every other column or row has
+    /// its preferred size, as with the `fr` unit of CSS grids.
+    Fraction(f32),
+}
+
 /// Combines the maximum, minimum and preferred sizes for a cell.
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SizeGrouping {
     pub minimum: Size,
     pub maximum: Size,
@@ -95,11 +181,39 @@ impl SizeGrouping {
         }
     }
 
+    /// Multiplies every size by a given factor. Unbounded maximums stay
+    /// unbounded.
+    pub fn scaled(&self, factor: f32) -> SizeGrouping {
+        let mut maximum = self.maximum.scaled(factor);
+        if self.maximum.width >= f32::MAX {
+            maximum.width = f32::MAX;
+        }
+        if self.maximum.height >= f32::MAX {
+            maximum.height = f32::MAX;
+        }
+
+        SizeGrouping {
+            minimum: self.minimum.scaled(factor),
+            preferred: self.preferred.scaled(factor),
+            maximum,
+        }
+    }
+
+    /// Spreads every size across a number of divisions. Unbounded
+    /// maximums stay unbounded.
     pub fn spread(&self, divisions: f32) -> SizeGrouping {
+        let mut maximum = self.maximum.spread(divisions);
+        if self.maximum.width >= f32::MAX {
+            maximum.width = f32::MAX;
+        }
+        if self.maximum.height >= f32::MAX {
+            maximum.height = f32::MAX;
+        }
+
         SizeGrouping {
             minimum: self.minimum.spread(divisions),
             preferred: self.preferred.spread(divisions),
-            maximum: self.maximum.spread(divisions),
+            maximum,
         }
     }
 
@@ -115,8 +229,9 @@ impl SizeGrouping {
     /// to layout rules specified by `flags`. Returns the X, Y coordinates
     /// as well as width and height of the box fitted to the area.
     pub fn box_fit(&self, area: &Size, prop: &CellProperties) -> (f32, f32, f32, f32) {
-        let pad_width = prop.padding.left + prop.padding.right;
-        let pad_height = prop.padding.top + prop.padding.bottom;
+        let padding = prop.squeezed_padding(&self.preferred, area);
+        let pad_width = padding.left + padding.right;
+        let pad_height = padding.top + padding.bottom;
 
         // combine maximum width and area width, depending on if fill has been activated
         let w = if prop.flags.contains(CellFlags::FillHorizontal) {
@@ -135,27 +250,27 @@ impl SizeGrouping {
         // find horizontal location of output box
         let x = if prop.flags.contains(CellFlags::AnchorRight) {
             // take size of the area and remove width, will anchor us to the right side
-            area.width - prop.padding.right - w
+            area.width - padding.right - w
         } else if prop.flags.contains(CellFlags::AnchorHorizontalCenter) {
             // tricky because we have to find the midpoint, then adjust by half of width
             // XXX this ought to still work, because the padding is on the "outside" of our center
             (area.width / 2.0) - (w / 2.0)
         } else {
             // AnchorLeft is the same as doing nothing, so we just put this on the left side.
-            prop.padding.left
+            padding.left
         };
 
         // find vertical location of output box
         let y = if prop.flags.contains(CellFlags::AnchorBottom) {
             // take size of the area and remove height, will anchor us to the top side
-            area.height - prop.padding.bottom - h
-        } else if prop.flags.contains(CellFlags::AnchorHorizontalCenter) {
+            area.height - padding.bottom - h
+        } else if prop.flags.contains(CellFlags::AnchorVerticalCenter) {
             // tricky because we have to find the midpoint, then adjust by half of height
             // XXX this ought to still work, because the padding is on the "outside" of our center
             (area.height / 2.0) - (h / 2.0)
         } else {
             // AnchorTop is the same as doing nothing, so we just put this on the top side.
-            prop.padding.top
+            padding.top
         };
 
         (x, y, w, h)
@@ -185,15 +300,54 @@ bitflags! {
         const AnchorHorizontalCenter = 0b0000_0001_0000_0000;
         /// Anchors the cell to the center of its available space, vertically.
         const AnchorVerticalCenter   = 0b0000_0010_0000_0000;
+        /// Cell will be the same width as all cells which are uniform horizontally.
+        const UniformHorizontal      = 0b0000_0100_0000_0000;
+        /// Cell will be the same height as all cells which are uniform vertically.
+        const UniformVertical        = 0b0000_1000_0000_0000;
+        /// Anchors the cell so its baseline lines up with the rest of its row.
+        const AnchorBaseline         = 0b0001_0000_0000_0000;
+        /// Anchors the cell to the side its table's columns start from.
+        const AnchorStart            = 0b0010_0000_0000_0000;
+        /// Anchors the cell to the side its table's columns end at.
+        const AnchorEnd              = 0b0100_0000_0000_0000;
         /// Cell will be the same size as all cells which are uniform.
-        const Uniform                = 0b0000_0100_0000_0000;
+        const Uniform                = Self::UniformHorizontal.bits | Self::UniformVertical.bits;
     }
 }
 
 /// Allows a closure to ensure a layout item has been placed where the
 /// layout engine decided it should go. The parameters are the `x`,
 /// `y` coordinates, and the `width`/`height` respectively.
-pub type PositioningFn = FnMut(f32, f32, f32, f32);
+pub type PositioningFn = dyn FnMut(f32, f32, f32, f32);
+
+/// Measures the content of a cell, such as text or an image, whose
+/// size is only known once the layout is imposed.
+pub trait Measure {
+    /// Returns the minimum, preferred and maximum sizes of the content.
+    fn measure(&mut self) -> SizeGrouping;
+
+    /// Returns the height the content needs when given `width`, for
+    /// content such as wrapped text whose height depends on its width.
+    /// This is asked once columns are sized but before rows are, and
+    /// overrides the preferred height. Content whose height does not
+    /// depend on its width returns `None`.
+    fn height_for_width(&mut self, _width: f32) -> Option<f32> {
+        None
+    }
+
+    /// Returns the distance from the top of the content to its
+    /// baseline, if it has one.
+    fn baseline(&mut self) -> Option<f32> {
+        None
+    }
+
+    /// Returns whether the content has changed since it was last
+    /// measured. Unchanged content keeps its previous measurement
+    /// instead of being measured again.
+    fn changed(&self) -> bool {
+        true
+    }
+}
 
 /// Encapsulates all properties for a cell; contributes to eventual layout decisions.
 pub struct CellProperties {
@@ -202,13 +356,64 @@ pub struct CellProperties {
     /// Controls various binary flags for the cell.
     pub flags: CellFlags,
     /// Controls how many columns this cell will occupy.
-    pub colspan: u8,
+    pub colspan: usize,
+    /// Controls how many rows this cell will occupy.
+    pub rowspan: usize,
     /// Controls how many pixels are intentionally wasted around this cell.
     pub padding: Rectangle,
+    /// Padding on the side the table's columns start from; added to
+    /// the left or right padding depending on the table's direction.
+    pub padding_start: f32,
+    /// Padding on the side the table's columns end at; added to the
+    /// left or right padding depending on the table's direction.
+    pub padding_end: f32,
+    /// Smallest the padding may be squeezed to when the table runs out
+    /// of room. `None` keeps the padding fixed.
+    pub sacrificial_padding: Option<Rectangle>,
+    /// Distance from the top of the cell's content to its baseline, for
+    /// cells anchored to their row's baseline.
+    pub baseline: Option<f32>,
+    /// Controls how many pixels are kept between this cell and its
+    /// neighbours. Unlike padding, spacing between two cells collapses
+    /// to the larger of the two and is dropped along the table's edges.
+    pub spacing: Rectangle,
+    /// Controls how quickly this cell's columns grow relative to other
+    /// expanding columns. Only used when expanding horizontally.
+    pub grow_horizontal: f32,
+    /// Controls how quickly this cell's rows grow relative to other
+    /// expanding rows. Only used when expanding vertically.
+    pub grow_vertical: f32,
+    /// Preferred width relative to the table, which takes the place of
+    /// the preferred width in `size` once the table's area is known.
+    pub relative_width: Option<Relative>,
+    /// Preferred height relative to the table, which takes the place of
+    /// the preferred height in `size` once the table's area is known.
+    pub relative_height: Option<Relative>,
+    /// Unit the sizes, padding, spacing and baseline of this cell are
+    /// given in. Measurements taken from content and nested tables are
+    /// always in physical pixels.
+    pub unit: Unit,
+    /// Controls whether this cell is shown and whether it takes up space.
+    pub visibility: Visibility,
+    /// Controls which columns and rows give up space first when the
+    /// table is too small. Those holding lower priority cells shrink to
+    /// their minimum before higher priority ones shrink at all.
+    pub shrink_priority: i32,
     /// Applies positioning updates for this cell. Note that this
     /// value always becomes `None` when cloned, so you cannot set
     /// default callbacks for cell policies.
     pub callback: Option<Box<PositioningFn>>,
+    /// Content which is measured to find this cell's sizes whenever a
+    /// layout is imposed. Like the callback, this value always becomes
+    /// `None` when cloned.
+    pub content: Option<Box<dyn Measure>>,
+    /// Most recent measurement taken from `content`; managed by the layout.
+    pub measured: Option<SizeGrouping>,
+    /// Nested table which is laid out within this cell. The cell's
+    /// sizes are taken from the table's own measurement, and the table
+    /// takes on the scale and em size of its parent. Like the
+    /// callback, this value always becomes `None` when cloned.
+    pub table: Option<Box<TableLayout>>,
 }
 
 impl Default for CellProperties {
@@ -217,8 +422,24 @@ impl Default for CellProperties {
             size: Default::default(),
             flags: CellFlags::None,
             padding: Default::default(),
+            padding_start: 0.0,
+            padding_end: 0.0,
+            sacrificial_padding: None,
+            baseline: None,
+            spacing: Default::default(),
+            grow_horizontal: 1.0,
+            grow_vertical: 1.0,
+            relative_width: None,
+            relative_height: None,
+            unit: Default::default(),
+            visibility: Default::default(),
+            shrink_priority: 0,
             colspan: 1,
+            rowspan: 1,
             callback: None,
+            content: None,
+            measured: None,
+            table: None,
         }
     }
 }
@@ -229,28 +450,429 @@ impl Clone for CellProperties {
             size: self.size.clone(),
             flags: self.flags,
             padding: self.padding,
+            padding_start: self.padding_start,
+            padding_end: self.padding_end,
+            sacrificial_padding: self.sacrificial_padding,
+            baseline: self.baseline,
+            spacing: self.spacing,
+            grow_horizontal: self.grow_horizontal,
+            grow_vertical: self.grow_vertical,
+            relative_width: self.relative_width,
+            relative_height: self.relative_height,
+            unit: self.unit,
+            visibility: self.visibility,
+            shrink_priority: self.shrink_priority,
             colspan: self.colspan,
+            rowspan: self.rowspan,
             callback: None,
+            content: None,
+            measured: None,
+            table: None,
         }
     }
 }
 
+/// Geometry decided by a call to `TableLayout::impose`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutResult {
+    /// Final placement of every cell, in the order the cells appear in
+    /// the layout. This is the order they were added in, unless cells
+    /// or rows have since been inserted or moved; `ids` tells which cell
+    /// each entry belongs to. Cells which do not take part in the layout
+    /// (such as those with a span of zero) receive an empty rectangle so
+    /// the indices stay aligned.
+    pub cells: Vec<Rectangle>,
+    /// Handle of every cell, indexed like `cells`.
+    pub ids: Vec<CellId>,
+    /// Final width of each column.
+    pub column_widths: Vec<f32>,
+    /// Final height of each row.
+    pub row_heights: Vec<f32>,
+    /// Visibility of every cell, indexed like `cells`. Hidden cells
+    /// still report the space kept for them, while collapsed cells
+    /// receive an empty rectangle.
+    pub visibility: Vec<Visibility>,
+}
+
+/// Reasons a layout could not be imposed within the space given.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    /// The minimum widths of the columns add up to more than the available width.
+    TooNarrow { minimum: f32, available: f32 },
+    /// The minimum heights of the rows add up to more than the available height.
+    TooShort { minimum: f32, available: f32 },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::TooNarrow { minimum, available } => write!(
+                f,
+                "columns need at least {} width but only {} is available",
+                minimum, available
+            ),
+            LayoutError::TooShort { minimum, available } => write!(
+                f,
+                "rows need at least {} height but only {} is available",
+                minimum, available
+            ),
+        }
+    }
+}
+
+impl error::Error for LayoutError {}
+
+impl LayoutResult {
+    /// Returns the placement of the cell with the given handle, or
+    /// `None` if the cell was not part of the layout.
+    pub fn cell(&self, id: CellId) -> Option<&Rectangle> {
+        self.ids
+            .iter()
+            .position(|cell| *cell == id)
+            .map(|i| &self.cells[i])
+    }
+}
+
+/// Tracks which columns are still covered by cells spanning down from
+/// earlier rows, so cells in later rows can flow around them.
+#[derive(Default)]
+struct Occupancy {
+    /// Column ranges taken by spanning cells, with how many rows each
+    /// stays covered for, counting the current row. Ranges end early
+    /// rather than past `usize::MAX`.
+    covered: Vec<(usize, usize, usize)>,
+    /// Next column a cell would be placed in.
+    column: usize,
+}
+
+impl Occupancy {
+    /// Returns the end of the range covering any of the given columns.
+    fn blocked(&self, start: usize, end: usize) -> Option<usize> {
+        self.covered
+            .iter()
+            .filter(|&&(s, e, _)| s < end && start < e)
+            .map(|&(_, e, _)| e)
+            .max()
+    }
+
+    /// Finds the first column at or after the cursor where a cell of
+    /// the given spans fits, then marks that space as taken. Returns
+    /// `None` for cells which span nothing and so do not exist, or
+    /// which would have to start past the last possible column.
+    fn place(&mut self, colspan: usize, rowspan: usize) -> Option<usize> {
+        if colspan == 0 || rowspan == 0 {
+            return None;
+        }
+
+        let mut start = self.column;
+        while let Some(end) = self.blocked(start, start.saturating_add(colspan)) {
+            start = end;
+        }
+
+        let end = start.saturating_add(colspan);
+        if start == end {
+            return None;
+        }
+
+        self.covered.push((start, end, rowspan));
+        self.column = end;
+        Some(start)
+    }
+
+    /// Returns the first free column at or after the cursor, which is
+    /// where the next single column cell would be placed.
+    fn next_free(&self) -> usize {
+        let mut column = self.column;
+        while let Some(end) = self.blocked(column, column.saturating_add(1)) {
+            column = end;
+        }
+        column
+    }
+
+    /// Moves to the start of the next row; spanning cells cover one row less.
+    fn next_row(&mut self) {
+        self.column = 0;
+        for range in &mut self.covered {
+            range.2 -= 1;
+        }
+        self.covered.retain(|range| range.2 > 0);
+    }
+}
+
+/// Where a cell ends up within the table's grid.
+struct Placement {
+    /// Index of the cell's opcode.
+    op: usize,
+    id: CellId,
+    row: usize,
+    /// Column the cell starts in, or `None` if it spans nothing.
+    column: Option<usize>,
+    colspan: usize,
+    rowspan: usize,
+}
+
+/// Intermediate results of sizing a table, before anything is placed.
+struct Plan {
+    /// Cells as they are laid out, indexed by insertion order. See
+    /// `CellProperties::resolve`.
+    cells: Vec<CellProperties>,
+    /// Cells as they were resolved, before uniform sizing was applied.
+    /// Sizes found later on are written here, so uniform cells can be
+    /// sized again from scratch.
+    resolved: Vec<CellProperties>,
+    columns: Vec<Track>,
+    rows: Vec<Track>,
+    /// Shared baseline of each row, measured from the top of the row.
+    row_baselines: Vec<f32>,
+    /// Space between each pair of neighbouring columns.
+    column_gaps: Vec<f32>,
+    /// Space between each pair of neighbouring rows.
+    row_gaps: Vec<f32>,
+}
+
+/// Size constraints of a single column or row, along the axis it is
+/// being solved for.
+struct Track {
+    minimum: f32,
+    /// Size once sacrificial padding has been squeezed out, but before
+    /// any content shrinks below its preferred size.
+    compressed: f32,
+    maximum: f32,
+    /// Size the track will be given; starts out as the preferred size.
+    size: f32,
+    /// Weight used when handing out extra space.
+    grow: f32,
+    /// Tracks with a lower priority shrink first.
+    priority: i32,
+    /// Share of leftover space claimed before expanding tracks grow.
+    fraction: f32,
+}
+
+impl Track {
+    fn new(
+        minimum: f32,
+        compressed: f32,
+        preferred: f32,
+        maximum: f32,
+        grow: f32,
+        priority: i32,
+        fraction: f32,
+    ) -> Self {
+        Track {
+            minimum,
+            compressed: f32::max(minimum, f32::min(compressed, preferred)),
+            maximum,
+            size: preferred,
+            grow,
+            priority,
+            fraction,
+        }
+    }
+
+    /// Returns the space needed to give every track its minimum size.
+    fn minimum(tracks: &[Track]) -> f32 {
+        tracks.iter().map(|t| t.minimum).sum()
+    }
+
+    /// Sizes a set of tracks so they fill `available` space as closely
+    /// as their constraints allow.
+    fn solve(tracks: &mut [Track], available: f32) {
+        // Error is what remains once we have given each track its preferred size.
+        let error = available - tracks.iter().map(|t| t.size).sum::<f32>();
+
+        if error > 0.0 {
+            // Extra space; fractions claim it first, then relax the layout if we need to
+            let error = Track::grow(tracks, error, |t| t.fraction);
+            Track::grow(tracks, error, |t| t.grow);
+        } else if error < 0.0 {
+            // Not enough space; tense up some more!
+            Track::shrink(tracks, -error);
+        }
+    }
+
+    /// Hands `extra` space out to tracks in proportion to their
+    /// `weight`. A track never grows past its maximum; whatever it
+    /// could not take goes around again to the tracks which can still
+    /// grow, until the space is used up or every track is capped.
+    /// Returns whatever space no track could take.
+    fn grow<F: Fn(&Track) -> f32>(tracks: &mut [Track], extra: f32, weight: F) -> f32 {
+        let mut extra = extra;
+        while extra > 0.0 {
+            let total_grow: f32 = tracks
+                .iter()
+                .filter(|t| weight(t) > 0.0 && t.size < t.maximum)
+                .map(&weight)
+                .sum();
+            if total_grow <= 0.0 {
+                break;
+            }
+
+            let mut leftover = 0.0;
+            for t in tracks.iter_mut() {
+                if weight(t) <= 0.0 || t.size >= t.maximum {
+                    continue;
+                }
+
+                let share = extra * (weight(t) / total_grow);
+                let room = t.maximum - t.size;
+                if share >= room {
+                    t.size = t.maximum;
+                    leftover += share - room;
+                } else {
+                    t.size += share;
+                }
+            }
+            extra = leftover;
+        }
+        extra
+    }
+
+    /// Takes `deficit` space away from tracks. Sacrificial padding is
+    /// squeezed out first; only then does content shrink below its
+    /// preferred size, down to its minimum. Content shrinks one
+    /// priority at a time, starting with the lowest.
+    fn shrink(tracks: &mut [Track], deficit: f32) {
+        let mut deficit = Track::take(tracks, deficit, |t| t.compressed);
+
+        let mut priorities: Vec<i32> = tracks.iter().map(|t| t.priority).collect();
+        priorities.sort();
+        priorities.dedup();
+        for priority in priorities {
+            // Tracks of other priorities have no slack to give this round.
+            deficit = Track::take(tracks, deficit, |t| {
+                if t.priority == priority {
+                    t.minimum
+                } else {
+                    t.size
+                }
+            });
+        }
+    }
+
+    /// Takes up to `deficit` space away from tracks, in proportion to
+    /// how far each track may shrink before reaching its `floor`.
+    /// Returns whatever could not be taken.
+    fn take<F: Fn(&Track) -> f32>(tracks: &mut [Track], deficit: f32, floor: F) -> f32 {
+        // We need to find slack space for each track
+        let total_slack: f32 = tracks
+            .iter()
+            .map(|t| f32::max(t.size - floor(t), 0.0))
+            .sum();
+
+        // Not enough slack; settle for the floors and pass on the rest.
+        if deficit >= total_slack {
+            for t in tracks.iter_mut() {
+                t.size = f32::min(t.size, floor(t));
+            }
+            return deficit - total_slack;
+        }
+
+        // spread error across slack space, proportionate to this areas slack participation
+        for t in tracks.iter_mut() {
+            let slack = f32::max(t.size - floor(t), 0.0);
+            let norm = slack / total_slack;
+            t.size = f32::max(t.size - deficit * norm, 0.0);
+        }
+        0.0
+    }
+}
+
+/// Whether a cell is shown, and whether it takes up space when it is not.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    /// The cell is laid out and shown as usual.
+    #[default]
+    Visible,
+    /// The cell is not shown, but its space is kept for it.
+    Hidden,
+    /// The cell is not shown and takes up no space at all.
+    Collapsed,
+}
+
+/// Handle to a cell within a `TableLayout`, returned when the cell is
+/// added. A handle stays valid as other cells are added or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellId(usize);
+
+// Most opcodes are cells, so boxing them would cost more than the
+// space wasted by row breaks.
+#[allow(clippy::large_enum_variant)]
 pub enum LayoutOp {
     /// Inserts a cell in the resulting layout.
-    Cell(CellProperties),
+    Cell(CellId, CellProperties),
     /// Inserts a row break in the resulting layout.
     Row,
 }
 
-#[derive(Default)]
+/// Order in which a table's columns are laid out.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// The first column is placed on the left.
+    #[default]
+    LeftToRight,
+    /// The first column is placed on the right, as used by scripts such
+    /// as Arabic and Hebrew.
+    RightToLeft,
+}
+
 pub struct TableLayout {
     pub cell_defaults: CellProperties,
-    pub row_defaults: BTreeMap<u8, CellProperties>,
-    pub column_defaults: BTreeMap<u8, CellProperties>,
+    pub row_defaults: BTreeMap<usize, CellProperties>,
+    pub column_defaults: BTreeMap<usize, CellProperties>,
     pub opcodes: Vec<LayoutOp>,
 
-    pub row: u8,
-    pub column: u8,
+    pub row: usize,
+    pub column: usize,
+
+    /// Space left between neighbouring columns.
+    pub column_gap: f32,
+    /// Space left between neighbouring rows.
+    pub row_gap: f32,
+    /// Space wasted around the edges of the whole table.
+    pub padding: Rectangle,
+    /// Order in which columns are laid out.
+    pub direction: Direction,
+    /// Number of physical pixels in a density independent pixel.
+    pub scale: f32,
+    /// Size of an em, in density independent pixels.
+    pub em: f32,
+    /// Unit the table's own padding and gaps are given in.
+    pub unit: Unit,
+    /// Grid which column and row boundaries are rounded to, in physical
+    /// pixels. `None` leaves coordinates unrounded.
+    pub snap: Option<f32>,
+    /// Whether the snapping grid is measured from the corner of the
+    /// table where its columns start, rather than from zero.
+    pub snap_relative: bool,
+
+    /// Handle given to the next cell which is added.
+    next_id: usize,
+    /// Columns covered by cells spanning down into the current row.
+    occupancy: Occupancy,
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        TableLayout {
+            cell_defaults: Default::default(),
+            row_defaults: Default::default(),
+            column_defaults: Default::default(),
+            opcodes: Default::default(),
+            row: 0,
+            column: 0,
+            column_gap: 0.0,
+            row_gap: 0.0,
+            padding: Default::default(),
+            direction: Default::default(),
+            scale: 1.0,
+            em: 16.0,
+            unit: Default::default(),
+            snap: None,
+            snap_relative: false,
+            next_id: 0,
+            occupancy: Default::default(),
+        }
+    }
 }
 
 impl CellProperties {
@@ -267,15 +889,13 @@ impl CellProperties {
     /// `CellProperties` for immediate insertion to a layout.
     pub fn with_defaults(layout: &TableLayout) -> Self {
         // try to get the column default
-        let column_value = layout.column_defaults.get(&layout.column);
-        if column_value.is_some() {
-            return (*column_value.unwrap()).clone();
+        if let Some(column_value) = layout.column_defaults.get(&layout.column) {
+            return column_value.clone();
         }
 
         // try to get the row default
-        let row_value = layout.row_defaults.get(&layout.row);
-        if row_value.is_some() {
-            return (*row_value.unwrap()).clone();
+        if let Some(row_value) = layout.row_defaults.get(&layout.row) {
+            return row_value.clone();
         }
 
         // just get the default i guess
@@ -284,6 +904,185 @@ impl CellProperties {
         }
     }
 
+    /// Works out the sizes this cell will be laid out with, taking the
+    /// measurements of its content and any nested table into account.
+    /// Percentages are taken of `space`, the room the table has for its
+    /// columns and rows, and are ignored while that is not yet known.
+    /// The cell's own sizes are converted to pixels by `factor`, and a
+    /// nested table takes on the `scale` and `em` size of its parent.
+    fn resolve_size(
+        &mut self,
+        space: Option<&Size>,
+        factor: f32,
+        scale: f32,
+        em: f32,
+    ) -> SizeGrouping {
+        let mut size = self.size.scaled(factor);
+
+        if let Some(content) = &mut self.content {
+            if self.measured.is_none() || content.changed() {
+                self.measured = Some(content.measure());
+            }
+        }
+        if let Some(measured) = &self.measured {
+            size = SizeGrouping::join(&size, measured);
+        }
+
+        if let Some(table) = &mut self.table {
+            table.scale = scale;
+            table.em = em;
+            size = SizeGrouping::join(&size, &table.measure());
+        }
+
+        // Fractions start out from the minimum and are handed their
+        // share of the leftover space when the tracks are solved.
+        let relative = |r: Option<Relative>, available: Option<f32>, min: f32, max: f32| match r {
+            Some(Relative::Percent(p)) => {
+                available.map(|a| f32::max(min, f32::min(a * p / 100.0, max)))
+            }
+            Some(Relative::Fraction(_)) => Some(min),
+            None => None,
+        };
+        if let Some(w) = relative(
+            self.relative_width,
+            space.map(|s| s.width),
+            size.minimum.width,
+            size.maximum.width,
+        ) {
+            size.preferred.width = w;
+        }
+        if let Some(h) = relative(
+            self.relative_height,
+            space.map(|s| s.height),
+            size.minimum.height,
+            size.maximum.height,
+        ) {
+            size.preferred.height = h;
+        }
+
+        size
+    }
+
+    /// Returns a copy of this cell as it will be laid out: sizes are
+    /// measured, the baseline is resolved and logical sides are mapped
+    /// onto physical ones. Tables are always laid out left to right and
+    /// mirrored afterwards if they run right to left, so in that case
+    /// physical sides are swapped here to come out right once mirrored.
+    /// Sizes relative to the table are taken of `space`, if it is known,
+    /// and lengths are converted to pixels using the table's `scale` and
+    /// `em` size.
+    fn resolve(
+        &mut self,
+        direction: Direction,
+        space: Option<&Size>,
+        scale: f32,
+        em: f32,
+    ) -> CellProperties {
+        let factor = self.unit.pixels(scale, em);
+        let mut resolved = self.clone();
+        resolved.size = self.resolve_size(space, factor, scale, em);
+        resolved.baseline = self.resolve_baseline(factor);
+        resolved.padding = self.padding.scaled(factor);
+        resolved.spacing = self.spacing.scaled(factor);
+        resolved.sacrificial_padding = self.sacrificial_padding.map(|p| p.scaled(factor));
+
+        if direction == Direction::RightToLeft {
+            let flags = resolved.flags;
+            resolved.flags.set(
+                CellFlags::AnchorLeft,
+                flags.contains(CellFlags::AnchorRight),
+            );
+            resolved.flags.set(
+                CellFlags::AnchorRight,
+                flags.contains(CellFlags::AnchorLeft),
+            );
+            mem::swap(&mut resolved.padding.left, &mut resolved.padding.right);
+            if let Some(minimum) = &mut resolved.sacrificial_padding {
+                mem::swap(&mut minimum.left, &mut minimum.right);
+            }
+            mem::swap(&mut resolved.spacing.left, &mut resolved.spacing.right);
+        }
+
+        if self.flags.contains(CellFlags::AnchorStart) {
+            resolved.flags |= CellFlags::AnchorLeft;
+        }
+        if self.flags.contains(CellFlags::AnchorEnd) {
+            resolved.flags |= CellFlags::AnchorRight;
+        }
+        resolved.padding.left += self.padding_start * factor;
+        resolved.padding.right += self.padding_end * factor;
+
+        resolved
+    }
+
+    /// Returns the smallest padding this cell can be squeezed to. Without
+    /// sacrificial padding this is simply the cell's padding.
+    fn padding_floor(&self) -> Rectangle {
+        match self.sacrificial_padding {
+            Some(minimum) => Rectangle {
+                top: f32::min(minimum.top, self.padding.top),
+                left: f32::min(minimum.left, self.padding.left),
+                bottom: f32::min(minimum.bottom, self.padding.bottom),
+                right: f32::min(minimum.right, self.padding.right),
+            },
+            None => self.padding,
+        }
+    }
+
+    /// Returns the padding left over once sacrificial padding has made
+    /// room for `content` within `area`. Each side gives up space in
+    /// proportion to how far it may be squeezed.
+    fn squeezed_padding(&self, content: &Size, area: &Size) -> Rectangle {
+        fn squeeze(near: f32, far: f32, near_min: f32, far_min: f32, excess: f32) -> (f32, f32) {
+            let slack = (near - near_min) + (far - far_min);
+            if excess <= 0.0 || slack <= 0.0 {
+                return (near, far);
+            }
+            let take = f32::min(excess, slack) / slack;
+            (
+                near - (near - near_min) * take,
+                far - (far - far_min) * take,
+            )
+        }
+
+        let floor = self.padding_floor();
+        let pad = self.padding;
+        let (left, right) = squeeze(
+            pad.left,
+            pad.right,
+            floor.left,
+            floor.right,
+            content.width + pad.left + pad.right - area.width,
+        );
+        let (top, bottom) = squeeze(
+            pad.top,
+            pad.bottom,
+            floor.top,
+            floor.bottom,
+            content.height + pad.top + pad.bottom - area.height,
+        );
+        Rectangle {
+            top,
+            left,
+            bottom,
+            right,
+        }
+    }
+
+    /// Finds this cell's baseline, if it is aligned along one. Content
+    /// which reports its own baseline takes precedence over the cell's.
+    /// Cells spanning several rows have no single row to line up with.
+    fn resolve_baseline(&mut self, factor: f32) -> Option<f32> {
+        if !self.flags.contains(CellFlags::AnchorBaseline) || self.rowspan != 1 {
+            return None;
+        }
+
+        self.content
+            .as_mut()
+            .and_then(|content| content.baseline())
+            .or(self.baseline.map(|b| b * factor))
+    }
+
     pub fn minimum_size(mut self, minimum: Size) -> Self {
         self.size.minimum = minimum;
         self
@@ -299,6 +1098,28 @@ impl CellProperties {
         self
     }
 
+    /// Sets the preferred width relative to the table. The minimum and
+    /// maximum widths still apply, so a cell can be a percentage of the
+    /// table but never narrower than its minimum.
+    pub fn relative_width(mut self, width: Relative) -> Self {
+        self.relative_width = Some(width);
+        self
+    }
+
+    /// Sets the preferred height relative to the table. The minimum and
+    /// maximum heights still apply.
+    pub fn relative_height(mut self, height: Relative) -> Self {
+        self.relative_height = Some(height);
+        self
+    }
+
+    /// Sets the unit this cell's sizes, padding, spacing and baseline
+    /// are given in.
+    pub fn unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
     pub fn expand(mut self) -> Self {
         self.flags |= CellFlags::ExpandHorizontal | CellFlags::ExpandVertical;
         self
@@ -314,6 +1135,36 @@ impl CellProperties {
         self
     }
 
+    /// Expands the cell in both directions, growing `weight` times as
+    /// fast as a cell with a weight of one.
+    pub fn grow(self, weight: f32) -> Self {
+        self.grow_horizontal(weight).grow_vertical(weight)
+    }
+
+    /// Expands the cell horizontally, growing `weight` times as fast
+    /// as a cell with a weight of one.
+    pub fn grow_horizontal(mut self, weight: f32) -> Self {
+        self.flags |= CellFlags::ExpandHorizontal;
+        self.grow_horizontal = weight;
+        self
+    }
+
+    /// Expands the cell vertically, growing `weight` times as fast
+    /// as a cell with a weight of one.
+    pub fn grow_vertical(mut self, weight: f32) -> Self {
+        self.flags |= CellFlags::ExpandVertical;
+        self.grow_vertical = weight;
+        self
+    }
+
+    /// Sets how reluctant this cell is to shrink. Columns and rows with
+    /// a lower priority are shrunk to their minimum size first; those
+    /// sharing a priority shrink together. The default priority is zero.
+    pub fn shrink_priority(mut self, priority: i32) -> Self {
+        self.shrink_priority = priority;
+        self
+    }
+
     pub fn fill(mut self) -> Self {
         self.flags |= CellFlags::FillHorizontal | CellFlags::FillVertical;
         self
@@ -349,6 +1200,33 @@ impl CellProperties {
         self
     }
 
+    /// Anchors the cell to the side its table's columns start from;
+    /// the left in left to right tables, and the right otherwise.
+    pub fn anchor_start(mut self) -> Self {
+        self.flags |= CellFlags::AnchorStart;
+        self
+    }
+
+    /// Anchors the cell to the side its table's columns end at; the
+    /// right in left to right tables, and the left otherwise.
+    pub fn anchor_end(mut self) -> Self {
+        self.flags |= CellFlags::AnchorEnd;
+        self
+    }
+
+    /// Anchors the cell so its baseline lines up with the baselines of
+    /// the other cells in its row.
+    pub fn anchor_baseline(mut self) -> Self {
+        self.flags |= CellFlags::AnchorBaseline;
+        self
+    }
+
+    /// Sets the distance from the top of the cell's content to its baseline.
+    pub fn baseline(mut self, offset: f32) -> Self {
+        self.baseline = Option::Some(offset);
+        self
+    }
+
     pub fn anchor_center(mut self) -> Self {
         self.flags |= CellFlags::AnchorHorizontalCenter | CellFlags::AnchorVerticalCenter;
         self
@@ -364,21 +1242,67 @@ impl CellProperties {
         self
     }
 
+    /// Sets whether this cell is shown and whether it takes up space.
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Hides this cell while keeping its space, so nothing around it moves.
+    pub fn hidden(self) -> Self {
+        self.visibility(Visibility::Hidden)
+    }
+
+    /// Hides this cell and gives its space to the rest of the table.
+    pub fn collapsed(self) -> Self {
+        self.visibility(Visibility::Collapsed)
+    }
+
     pub fn uniform(mut self) -> Self {
         self.flags |= CellFlags::Uniform;
         self
     }
 
-    pub fn colspan(mut self, span: u8) -> Self {
+    pub fn uniform_horizontal(mut self) -> Self {
+        self.flags |= CellFlags::UniformHorizontal;
+        self
+    }
+
+    pub fn uniform_vertical(mut self) -> Self {
+        self.flags |= CellFlags::UniformVertical;
+        self
+    }
+
+    pub fn colspan(mut self, span: usize) -> Self {
         self.colspan = span;
         self
     }
 
+    pub fn rowspan(mut self, span: usize) -> Self {
+        self.rowspan = span;
+        self
+    }
+
     pub fn callback(mut self, fun: Box<PositioningFn>) -> Self {
         self.callback = Option::Some(fun);
         self
     }
 
+    /// Measures `content` to find this cell's sizes whenever a layout
+    /// is imposed. The measurement is combined with any sizes set on
+    /// the cell, so those still act as limits.
+    pub fn content(mut self, content: Box<dyn Measure>) -> Self {
+        self.content = Option::Some(content);
+        self
+    }
+
+    /// Nests a table within this cell. The table is imposed on the
+    /// area this cell receives whenever the parent table is imposed.
+    pub fn table(mut self, table: TableLayout) -> Self {
+        self.table = Option::Some(Box::new(table));
+        self
+    }
+
     /// Sets the padding around this cell to the supplied top, left, right and bottom values as
     /// specified by a rectangle struct.
     pub fn padding(mut self, pad: &Rectangle) -> Self {
@@ -417,6 +1341,65 @@ impl CellProperties {
         self.padding.right = pad;
         self
     }
+
+    /// Allows the padding around this cell to be squeezed down to the
+    /// supplied `minimum` before the cell's content is shrunk below its
+    /// preferred size.
+    pub fn sacrificial_padding(mut self, minimum: &Rectangle) -> Self {
+        self.sacrificial_padding = Some(*minimum);
+        self
+    }
+
+    /// Sets the padding on the side the table's columns start from.
+    pub fn padding_start(mut self, pad: f32) -> Self {
+        self.padding_start = pad;
+        self
+    }
+
+    /// Sets the padding on the side the table's columns end at.
+    pub fn padding_end(mut self, pad: f32) -> Self {
+        self.padding_end = pad;
+        self
+    }
+
+    /// Sets the spacing around this cell to the supplied top, left, right and bottom values as
+    /// specified by a rectangle struct.
+    pub fn spacing(mut self, space: &Rectangle) -> Self {
+        self.spacing = *space;
+        self
+    }
+
+    pub fn spacing_all(mut self, space: f32) -> Self {
+        self.spacing.top = space;
+        self.spacing.left = space;
+        self.spacing.bottom = space;
+        self.spacing.right = space;
+        self
+    }
+
+    /// Sets the spacing on the top side of this cell.
+    pub fn spacing_top(mut self, space: f32) -> Self {
+        self.spacing.top = space;
+        self
+    }
+
+    /// Sets the spacing on the left side of this cell.
+    pub fn spacing_left(mut self, space: f32) -> Self {
+        self.spacing.left = space;
+        self
+    }
+
+    /// Sets the spacing on the bottom side of this cell.
+    pub fn spacing_bottom(mut self, space: f32) -> Self {
+        self.spacing.bottom = space;
+        self
+    }
+
+    /// Sets the spacing on the right side of this cell.
+    pub fn spacing_right(mut self, space: f32) -> Self {
+        self.spacing.right = space;
+        self
+    }
 }
 
 impl TableLayout {
@@ -424,26 +1407,68 @@ impl TableLayout {
         Default::default()
     }
 
+    /// Gives every uniform cell the largest minimum and preferred size
+    /// found among the cells which are uniform along the same axis.
+    fn apply_uniform(cells: &mut [CellProperties]) {
+        let mut uniform = SizeGrouping::default();
+        for cp in cells.iter() {
+            // Collapsed cells take no space, so they have no say.
+            if cp.visibility == Visibility::Collapsed {
+                continue;
+            }
+            if cp.flags.contains(CellFlags::UniformHorizontal) {
+                uniform.minimum.width = f32::max(uniform.minimum.width, cp.size.minimum.width);
+                uniform.preferred.width =
+                    f32::max(uniform.preferred.width, cp.size.preferred.width);
+            }
+            if cp.flags.contains(CellFlags::UniformVertical) {
+                uniform.minimum.height = f32::max(uniform.minimum.height, cp.size.minimum.height);
+                uniform.preferred.height =
+                    f32::max(uniform.preferred.height, cp.size.preferred.height);
+            }
+        }
+
+        for cp in cells.iter_mut() {
+            if cp.flags.contains(CellFlags::UniformHorizontal) {
+                cp.size.minimum.width = uniform.minimum.width;
+                cp.size.preferred.width = uniform.preferred.width;
+            }
+            if cp.flags.contains(CellFlags::UniformVertical) {
+                cp.size.minimum.height = uniform.minimum.height;
+                cp.size.preferred.height = uniform.preferred.height;
+            }
+        }
+    }
+
     /// Calculates the number of rows and columns which exist in this table layout.
-    pub fn get_rows_cols(&self) -> (u8, u8) {
+    /// Only row breaks and the columns cells start in count; a span reaching past
+    /// the last row or column is cut short there instead of adding more.
+    pub fn get_rows_cols(&self) -> (usize, usize) {
+        let mut occupancy = Occupancy::default();
         let mut cols = 0;
-        let mut colcur = 0;
         let mut rows = 0;
+        let mut row = 0;
+        let mut row_used = false;
 
         for op in &self.opcodes {
             match op {
-                LayoutOp::Cell(cp) => colcur += cp.colspan,
+                LayoutOp::Cell(_, cp) => {
+                    if let Some(col) = occupancy.place(cp.colspan, cp.rowspan) {
+                        cols = max(cols, col + 1);
+                        row_used = true;
+                    }
+                }
                 LayoutOp::Row => {
-                    cols = max(cols, colcur);
-                    colcur = 0;
-                    rows += 1
+                    occupancy.next_row();
+                    row += 1;
+                    rows = max(rows, row);
+                    row_used = false;
                 }
             }
         }
 
-        if colcur > 0 {
-            cols = max(cols, colcur);
-            rows += 1;
+        if row_used {
+            rows = max(rows, row + 1);
         }
 
         (rows, cols)
@@ -453,6 +1478,7 @@ impl TableLayout {
     pub fn clear(&mut self) {
         self.row = 0;
         self.column = 0;
+        self.occupancy = Default::default();
         self.opcodes.clear()
     }
 
@@ -461,231 +1487,1038 @@ impl TableLayout {
         self.clear();
         self.row_defaults.clear();
         self.column_defaults.clear();
-        self.cell_defaults = Default::default()
+        self.cell_defaults = Default::default();
+        self.column_gap = 0.0;
+        self.row_gap = 0.0;
+        self.padding = Default::default();
+        self.direction = Default::default();
+        self.scale = 1.0;
+        self.em = 16.0;
+        self.unit = Default::default();
+        self.snap = None;
+        self.snap_relative = false;
+    }
+
+    /// Sets the order in which columns are laid out. Right to left
+    /// tables place their first column on the right. Physical anchors
+    /// and padding keep their sides, while start and end follow the
+    /// direction.
+    pub fn with_direction(&mut self, direction: Direction) -> &mut Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Sets the padding around the edges of the whole table. Padding is
+    /// removed from the table's area before columns and rows are sized.
+    pub fn with_padding(&mut self, padding: &Rectangle) -> &mut Self {
+        self.padding = *padding;
+        self
+    }
+
+    /// Sets the number of physical pixels in a density independent pixel,
+    /// such as 2 on a high density display. Callbacks and results are
+    /// always given in physical pixels. Nested tables take on the scale
+    /// and em size of the table they are in.
+    pub fn with_scale(&mut self, scale: f32) -> &mut Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Sets the size of an em, in density independent pixels.
+    pub fn with_em(&mut self, em: f32) -> &mut Self {
+        self.em = em;
+        self
+    }
+
+    /// Sets the unit the table's own padding and gaps are given in.
+    pub fn with_unit(&mut self, unit: Unit) -> &mut Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Rounds the boundaries between columns and rows, and the edges of
+    /// every cell, to multiples of `quantum` in the coordinates callbacks
+    /// receive. A quantum of one snaps to whole pixels. Boundaries are
+    /// rounded rather than sizes, so neighbouring cells still share their
+    /// edges. The table's own edges are rounded inwards, so no cell is
+    /// pushed outside of the table; tracks which fill a table lying on
+    /// the grid still add up to it exactly.
+    pub fn with_snapping(&mut self, quantum: f32) -> &mut Self {
+        self.snap = Some(quantum);
+        self.snap_relative = false;
+        self
+    }
+
+    /// Like `with_snapping`, but measures the grid from the corner of the
+    /// table where its columns start, as suits a design grid. The table's
+    /// far edges are never moved, so tracks which fill the table add up to
+    /// it exactly even when it is not a multiple of `quantum`.
+    pub fn with_relative_snapping(&mut self, quantum: f32) -> &mut Self {
+        self.snap = Some(quantum);
+        self.snap_relative = true;
+        self
+    }
+
+    /// Rounds `value` to the nearest multiple of `quantum` away from
+    /// `origin`, if there is one.
+    fn snap(value: f32, origin: f32, quantum: Option<f32>) -> f32 {
+        match quantum {
+            Some(q) if q > 0.0 => origin + ((value - origin) / q).round() * q,
+            _ => value,
+        }
+    }
+
+    /// Snaps the boundaries between tracks which begin at `offsets` and
+    /// are `sizes` long. Both ends of every track are snapped rather than
+    /// its size, so rounding never accumulates along the table. No
+    /// boundary is moved past the table's edges, which are the start of
+    /// the first track and `far`. When `pinned`, those edges stay where
+    /// they are, so tracks which fill the table still add up to it exactly;
+    /// otherwise they are rounded inwards onto the grid. Tracks overflowing
+    /// the table keep their ends.
+    fn snap_tracks(
+        offsets: &mut [f32],
+        sizes: &mut [f32],
+        origin: f32,
+        far: f32,
+        quantum: f32,
+        pinned: bool,
+    ) {
+        let (near, end) = match (offsets.first(), offsets.last(), sizes.last()) {
+            (Some(first), Some(last), Some(size)) => (*first, last + size),
+            _ => return,
+        };
+        let far = if end > far + quantum { end } else { far };
+        let (near, far) = if pinned {
+            (near, far)
+        } else {
+            let grid =
+                |v: f32, round: fn(f32) -> f32| origin + round((v - origin) / quantum) * quantum;
+            (grid(near, f32::ceil), grid(far, f32::floor))
+        };
+        let snap = |v: f32| {
+            let v = TableLayout::snap(v, origin, Some(quantum));
+            f32::max(near, f32::min(v, far))
+        };
+
+        for (i, (offset, size)) in offsets.iter_mut().zip(sizes.iter_mut()).enumerate() {
+            let start = if i == 0 { near } else { snap(*offset) };
+            *size = snap(*offset + *size) - start;
+            *offset = start;
+        }
+    }
+
+    /// Returns the table's padding in physical pixels.
+    fn table_padding(&self) -> Rectangle {
+        self.padding.scaled(self.unit.pixels(self.scale, self.em))
+    }
+
+    /// Sets the space left between neighbouring columns and rows. Gaps
+    /// are only placed between cells, never around the table's edges.
+    /// Cells which ask for more spacing than the gap still get it.
+    pub fn with_gaps(&mut self, column_gap: f32, row_gap: f32) -> &mut Self {
+        self.column_gap = column_gap;
+        self.row_gap = row_gap;
+        self
     }
 
     /// Adds a new row to the layout.
     pub fn with_row(&mut self) -> &mut Self {
         self.opcodes.push(LayoutOp::Row);
-        self.row += 1;
-        self.column = 0;
+        self.row = self.row.saturating_add(1);
+        self.occupancy.next_row();
+        self.column = self.occupancy.next_free();
         self
     }
 
-    /// Hands the cell off to the layout.
-    pub fn with_cell(&mut self, properties: CellProperties) -> &mut Self {
-        self.column += properties.colspan;
-        self.opcodes.push(LayoutOp::Cell(properties));
-        self
+    /// Hands the cell off to the layout. Returns a handle which can be
+    /// used to look the cell up again later.
+    pub fn with_cell(&mut self, properties: CellProperties) -> CellId {
+        let id = CellId(self.next_id);
+        self.next_id += 1;
+        self.occupancy.place(properties.colspan, properties.rowspan);
+        self.column = self.occupancy.next_free();
+        self.opcodes.push(LayoutOp::Cell(id, properties));
+        id
     }
 
-    pub fn impose(&mut self, width: f32, height: f32) {
-        let mut row: u8 = 0;
-        let mut col: u8 = 0;
+    /// Returns the properties of the cell with the given handle, or
+    /// `None` if the cell is no longer part of the layout.
+    pub fn cell(&self, id: CellId) -> Option<&CellProperties> {
+        self.opcodes.iter().find_map(|op| match op {
+            LayoutOp::Cell(cell, cp) if *cell == id => Some(cp),
+            _ => None,
+        })
+    }
 
-        let (total_rows, total_cols) = self.get_rows_cols();
-        if total_cols == 0 {
-            return;
-        } // short-circuiting opportunity
+    /// Returns the properties of the cell with the given handle for
+    /// modification, or `None` if the cell is no longer part of the
+    /// layout. Changes take effect the next time the layout is imposed.
+    pub fn cell_mut(&mut self, id: CellId) -> Option<&mut CellProperties> {
+        self.opcodes.iter_mut().find_map(|op| match op {
+            LayoutOp::Cell(cell, cp) if *cell == id => Some(cp),
+            _ => None,
+        })
+    }
+
+    /// Returns the row and column the cell with the given handle is
+    /// placed at, or `None` if it is not placed anywhere.
+    pub fn position(&self, id: CellId) -> Option<(usize, usize)> {
+        self.placements()
+            .into_iter()
+            .find(|p| p.id == id)
+            .and_then(|p| p.column.map(|column| (p.row, column)))
+    }
+
+    /// Returns the handle of the cell covering the given row and column,
+    /// taking spans into account.
+    pub fn cell_at(&self, row: usize, column: usize) -> Option<CellId> {
+        self.placements()
+            .into_iter()
+            .find(|p| match p.column {
+                Some(c) => {
+                    (p.row..p.row + p.rowspan).contains(&row)
+                        && (c..c + p.colspan).contains(&column)
+                }
+                None => false,
+            })
+            .map(|p| p.id)
+    }
+
+    /// Inserts an empty row so that it becomes row number `row`; the
+    /// rows from there on move down by one, along with their defaults,
+    /// and cells spanning down across it span one row more. Returns
+    /// whether the row was inserted; nothing changes if `row` is more
+    /// than one past the last row.
+    pub fn insert_row(&mut self, row: usize) -> bool {
+        if row > self.row + 1 {
+            return false;
+        }
+        for p in self.placements() {
+            if p.column.is_some() && p.row < row && p.row + p.rowspan > row {
+                if let LayoutOp::Cell(_, cp) = &mut self.opcodes[p.op] {
+                    cp.rowspan = cp.rowspan.saturating_add(1);
+                }
+            }
+        }
+        self.insert_row_break(row);
+        true
+    }
+
+    /// Inserts the row break which starts row number `row`, leaving
+    /// the spans of cells alone.
+    fn insert_row_break(&mut self, row: usize) {
+        let at = self
+            .row_ops(row)
+            .map_or(self.opcodes.len(), |range| range.start);
+        self.opcodes.insert(at, LayoutOp::Row);
+        self.shift_row_defaults(|r| if r >= row { r + 1 } else { r });
+        self.sync_cursors();
+    }
+
+    /// Removes a row along with all of its cells and its defaults; the
+    /// rows after it move up by one, and cells spanning down across it
+    /// span one row less. Returns the removed cells, or `None` if there
+    /// is no such row.
+    pub fn remove_row(&mut self, row: usize) -> Option<Vec<CellProperties>> {
+        self.row_ops(row)?;
+        for p in self.placements() {
+            if p.column.is_some() && p.row < row && p.row + p.rowspan > row {
+                if let LayoutOp::Cell(_, cp) = &mut self.opcodes[p.op] {
+                    cp.rowspan -= 1;
+                }
+            }
+        }
+
+        let cells = self.take_row(row)?;
+        self.row_defaults.remove(&row);
+        self.shift_row_defaults(|r| if r > row { r - 1 } else { r });
+        self.sync_cursors();
+        Some(
+            cells
+                .into_iter()
+                .filter_map(|op| match op {
+                    LayoutOp::Cell(_, cp) => Some(cp),
+                    LayoutOp::Row => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Moves a row, its cells and its defaults so that it becomes row
+    /// number `to`. Returns whether the row was moved; nothing changes
+    /// if there is no row `from`, or if `to` is past the last row.
+    pub fn move_row(&mut self, from: usize, to: usize) -> bool {
+        if to > self.row {
+            return false;
+        }
+        if from == to {
+            return self.row_ops(from).is_some();
+        }
 
-        let mut col_sizes: Vec<SizeGrouping> = Vec::with_capacity(total_cols as usize);
-        // XXX resize_with is unstable, but would do what we want just fine
-        for _i in 0..total_cols {
-            col_sizes.push(Default::default());
+        let cells = match self.take_row(from) {
+            Some(cells) => cells,
+            None => return false,
+        };
+        let default = self.row_defaults.remove(&from);
+        self.shift_row_defaults(|r| if r > from { r - 1 } else { r });
+        self.sync_cursors();
+
+        self.insert_row_break(to);
+        let at = self.row_ops(to).unwrap().start;
+        self.opcodes.splice(at..at, cells);
+        if let Some(default) = default {
+            self.row_defaults.insert(to, default);
         }
+        self.sync_cursors();
+        true
+    }
 
-        // XXX resize_with is unstable, but would do what we want just fine
-        let mut row_sizes: Vec<SizeGrouping> = Vec::with_capacity(total_cols as usize);
-        for _i in 0..total_rows {
-            row_sizes.push(Default::default());
+    /// Inserts a cell into `row`, just before the first cell of that
+    /// row which is placed at or after `column`. Returns the new cell's
+    /// handle, or `None` if there is no such row.
+    pub fn insert_cell(
+        &mut self,
+        row: usize,
+        column: usize,
+        properties: CellProperties,
+    ) -> Option<CellId> {
+        let id = CellId(self.next_id);
+        if !self.put_cell(row, column, id, properties) {
+            return None;
         }
+        self.next_id += 1;
+        Some(id)
+    }
 
-        let mut has_xexpand: Vec<bool> = Vec::with_capacity(total_cols as usize);
-        for _i in 0..total_cols {
-            has_xexpand.push(false);
+    /// Puts a cell with an existing handle into `row`, just before the
+    /// first cell of that row which is placed at or after `column`.
+    /// Returns whether there was such a row.
+    fn put_cell(
+        &mut self,
+        row: usize,
+        column: usize,
+        id: CellId,
+        properties: CellProperties,
+    ) -> bool {
+        let range = match self.row_ops(row) {
+            Some(range) => range,
+            None => return false,
+        };
+        let at = self
+            .placements()
+            .into_iter()
+            .find(|p| p.row == row && p.column.is_some_and(|c| c >= column))
+            .map_or(range.end, |p| p.op);
+
+        self.opcodes.insert(at, LayoutOp::Cell(id, properties));
+        self.sync_cursors();
+        true
+    }
+
+    /// Removes the cell with the given handle from the layout. Returns
+    /// its properties, or `None` if it was not part of the layout.
+    pub fn remove_cell(&mut self, id: CellId) -> Option<CellProperties> {
+        let at = self.placements().into_iter().find(|p| p.id == id)?.op;
+        match self.opcodes.remove(at) {
+            LayoutOp::Cell(_, cp) => {
+                self.sync_cursors();
+                Some(cp)
+            }
+            LayoutOp::Row => None,
         }
+    }
+
+    /// Removes the cell covering the given row and column. Returns its
+    /// properties, or `None` if no cell covers that spot.
+    pub fn remove_cell_at(&mut self, row: usize, column: usize) -> Option<CellProperties> {
+        let id = self.cell_at(row, column)?;
+        self.remove_cell(id)
+    }
 
-        let mut has_yexpand: Vec<bool> = Vec::with_capacity(total_rows as usize);
-        for _i in 0..total_rows {
-            has_yexpand.push(false);
+    /// Moves the cell with the given handle into `row`, just before the
+    /// first cell of that row which is placed at or after `column`. The
+    /// cell keeps its handle. Returns whether the cell was moved;
+    /// nothing changes if there is no such cell or no such row.
+    pub fn move_cell(&mut self, id: CellId, row: usize, column: usize) -> bool {
+        if self.row_ops(row).is_none() {
+            return false;
+        }
+        match self.remove_cell(id) {
+            Some(cp) => self.put_cell(row, column, id, cp),
+            None => false,
         }
+    }
 
-        // We determine size preferences for each column in the layout.
+    /// Returns where every cell of the layout ends up, in the order the
+    /// cells appear in the opcodes.
+    /// Spans are cut short at the edges of the table.
+    fn placements(&self) -> Vec<Placement> {
+        let (rows, cols) = self.get_rows_cols();
+        let mut placements = Vec::new();
+        let mut occupancy = Occupancy::default();
+        let mut row = 0;
+        for (op, opcode) in self.opcodes.iter().enumerate() {
+            match opcode {
+                LayoutOp::Cell(id, cp) => {
+                    let column = occupancy.place(cp.colspan, cp.rowspan);
+                    let col = column.unwrap_or(0);
+                    placements.push(Placement {
+                        op,
+                        id: *id,
+                        row,
+                        column,
+                        colspan: min(cp.colspan, cols.saturating_sub(col)),
+                        rowspan: min(cp.rowspan, rows.saturating_sub(row)),
+                    })
+                }
+                LayoutOp::Row => {
+                    occupancy.next_row();
+                    row += 1;
+                }
+            }
+        }
+        placements
+    }
+
+    /// Returns the range of opcodes holding the cells of `row`, or
+    /// `None` if there is no such row.
+    fn row_ops(&self, row: usize) -> Option<Range<usize>> {
+        let mut current = 0;
+        let mut start = 0;
+        for (i, op) in self.opcodes.iter().enumerate() {
+            if let LayoutOp::Row = op {
+                if current == row {
+                    return Some(start..i);
+                }
+                current += 1;
+                start = i + 1;
+            }
+        }
+        if current == row {
+            Some(start..self.opcodes.len())
+        } else {
+            None
+        }
+    }
+
+    /// Takes a row's cells out of the opcodes, along with the row break
+    /// which separates it from its neighbours.
+    fn take_row(&mut self, row: usize) -> Option<Vec<LayoutOp>> {
+        let range = self.row_ops(row)?;
+        let cells: Vec<LayoutOp> = self.opcodes.drain(range.clone()).collect();
+        if range.start < self.opcodes.len() {
+            // The row ended in a break.
+            self.opcodes.remove(range.start);
+        } else if range.start > 0 {
+            // The last row; drop the break leading into it instead.
+            self.opcodes.remove(range.start - 1);
+        }
+        Some(cells)
+    }
+
+    /// Moves row defaults to the rows `f` gives for their old rows.
+    fn shift_row_defaults<F: Fn(usize) -> usize>(&mut self, f: F) {
+        let defaults = mem::take(&mut self.row_defaults);
+        self.row_defaults = defaults.into_iter().map(|(r, cp)| (f(r), cp)).collect();
+    }
+
+    /// Puts the row and column cursors back where `with_row` and
+    /// `with_cell` would have left them after building the opcodes.
+    fn sync_cursors(&mut self) {
+        self.row = 0;
+        self.occupancy = Default::default();
         for op in &self.opcodes {
             match op {
-                LayoutOp::Cell(cp) => {
-                    match cp.colspan {
-                        // If a cell has a span of zero, that is kind of stupid and it basically doesn't exist.
-                        0 => {}
-                        _ => {
-                            let midget = cp.size.padded(cp.padding).spread(f32::from(cp.colspan));
-                            row_sizes[row as usize] =
-                                SizeGrouping::join(&row_sizes[row as usize], &cp.size);
-                            if cp.flags.contains(CellFlags::ExpandVertical) {
-                                has_yexpand[row as usize] = true
-                            }
-                            for _i in 0..cp.colspan {
-                                if cp.flags.contains(CellFlags::ExpandHorizontal) {
-                                    has_xexpand[col as usize] = true
-                                }
-                                col_sizes[col as usize] =
-                                    SizeGrouping::join(&col_sizes[col as usize], &midget);
-                                col += 1;
-                            }
+                LayoutOp::Cell(_, cp) => {
+                    self.occupancy.place(cp.colspan, cp.rowspan);
+                }
+                LayoutOp::Row => {
+                    self.row += 1;
+                    self.occupancy.next_row();
+                }
+            }
+        }
+        self.column = self.occupancy.next_free();
+    }
+
+    /// Works out the sizes every cell, column and row would like to
+    /// have, without deciding on any final sizes.
+    fn plan(&mut self) -> Plan {
+        self.plan_in(None)
+    }
+
+    /// Works out what every column and row would like to be, with sizes
+    /// relative to the table taken of `space`, if it is known.
+    fn plan_in(&mut self, space: Option<&Size>) -> Plan {
+        // Resolve the properties each cell will be laid out with.
+        let direction = self.direction;
+        let (scale, em) = (self.scale, self.em);
+        let resolved: Vec<CellProperties> = self
+            .opcodes
+            .iter_mut()
+            .filter_map(|op| match op {
+                LayoutOp::Cell(_, cp) => Some(cp.resolve(direction, space, scale, em)),
+                LayoutOp::Row => None,
+            })
+            .collect();
+
+        self.aggregate(resolved)
+    }
+
+    /// Works out the sizes every column and row would like to have from
+    /// the sizes of the `resolved` cells within them, once uniform cells
+    /// have been sized alike.
+    fn aggregate(&self, resolved: Vec<CellProperties>) -> Plan {
+        let mut cells = resolved.clone();
+        TableLayout::apply_uniform(&mut cells);
+
+        let mut row: usize = 0;
+
+        let (total_rows, total_cols) = self.get_rows_cols();
+
+        let mut col_sizes: Vec<SizeGrouping> = vec![Default::default(); total_cols];
+        let mut row_sizes: Vec<SizeGrouping> = vec![Default::default(); total_rows];
+        let mut col_compressed: Vec<f32> = vec![0.0; total_cols];
+        let mut row_compressed: Vec<f32> = vec![0.0; total_rows];
+        let mut col_grow: Vec<f32> = vec![0.0; total_cols];
+        let mut col_priority: Vec<i32> = vec![i32::MIN; total_cols];
+        let mut row_priority: Vec<i32> = vec![i32::MIN; total_rows];
+        let mut row_grow: Vec<f32> = vec![0.0; total_rows];
+        let mut col_fraction: Vec<f32> = vec![0.0; total_cols];
+        let mut row_fraction: Vec<f32> = vec![0.0; total_rows];
+        let factor = self.unit.pixels(self.scale, self.em);
+        let mut column_gaps: Vec<f32> =
+            vec![self.column_gap * factor; total_cols.saturating_sub(1)];
+        let mut row_gaps: Vec<f32> = vec![self.row_gap * factor; total_rows.saturating_sub(1)];
+        // Room needed above and below each row's baseline.
+        let mut above_baseline: Vec<f32> = vec![0.0; total_rows];
+        let mut below_baseline: Vec<f32> = vec![0.0; total_rows];
+        // Whether each track holds any cells, and whether any of them are not collapsed.
+        let mut col_occupied: Vec<bool> = vec![false; total_cols];
+        let mut row_occupied: Vec<bool> = vec![false; total_rows];
+        let mut col_shown: Vec<bool> = vec![false; total_cols];
+        let mut row_shown: Vec<bool> = vec![false; total_rows];
+
+        // We determine size preferences for each column and row in the layout.
+        let mut occupancy = Occupancy::default();
+        let mut cell = 0;
+        for op in &self.opcodes {
+            match op {
+                LayoutOp::Cell(_, cp) => {
+                    let rc = &cells[cell];
+                    cell += 1;
+
+                    // If a cell has a span of zero, that is kind of stupid and it basically doesn't exist.
+                    let col = match occupancy.place(cp.colspan, cp.rowspan) {
+                        Some(col) => col,
+                        None => continue,
+                    };
+                    // Spans reaching past the edges of the table stop there.
+                    let colspan = min(cp.colspan, total_cols - col);
+                    let rowspan = min(cp.rowspan, total_rows - row);
+
+                    // Collapsed cells keep their place in the grid, but nothing more.
+                    let shown = rc.visibility != Visibility::Collapsed;
+                    for c in col..col + colspan {
+                        col_occupied[c] = true;
+                        col_shown[c] |= shown;
+                    }
+                    for r in row..row + rowspan {
+                        row_occupied[r] = true;
+                        row_shown[r] |= shown;
+                    }
+                    if !shown {
+                        continue;
+                    }
+
+                    // Sacrificial padding lowers the minimum, and gives the
+                    // size the cell can shrink to before its content does.
+                    let floor = rc.padding_floor();
+                    let mut padded = rc.size.padded(rc.padding);
+                    padded.minimum = rc.size.minimum.padded(floor);
+                    let compressed = rc.size.preferred.padded(floor);
+
+                    let col_part = padded.spread(colspan as f32);
+                    let row_part = padded.spread(rowspan as f32);
+                    for r in row..row + rowspan {
+                        if rc.flags.contains(CellFlags::ExpandVertical) {
+                            row_grow[r] = f32::max(row_grow[r], rc.grow_vertical)
                         }
+                        if let Some(Relative::Fraction(f)) = rc.relative_height {
+                            row_fraction[r] = f32::max(row_fraction[r], f);
+                        }
+                        row_priority[r] = i32::max(row_priority[r], rc.shrink_priority);
+                        row_compressed[r] =
+                            f32::max(row_compressed[r], compressed.height / rowspan as f32);
+                        row_sizes[r] = SizeGrouping::join(&row_sizes[r], &row_part);
+                    }
+                    for c in col..col + colspan {
+                        if rc.flags.contains(CellFlags::ExpandHorizontal) {
+                            col_grow[c] = f32::max(col_grow[c], rc.grow_horizontal)
+                        }
+                        if let Some(Relative::Fraction(f)) = rc.relative_width {
+                            col_fraction[c] = f32::max(col_fraction[c], f);
+                        }
+                        col_priority[c] = i32::max(col_priority[c], rc.shrink_priority);
+                        col_compressed[c] =
+                            f32::max(col_compressed[c], compressed.width / colspan as f32);
+                        col_sizes[c] = SizeGrouping::join(&col_sizes[c], &col_part);
+                    }
+
+                    if let Some(baseline) = rc.baseline {
+                        above_baseline[row] =
+                            f32::max(above_baseline[row], rc.padding.top + baseline);
+                        below_baseline[row] = f32::max(
+                            below_baseline[row],
+                            rc.size.preferred.height - baseline + rc.padding.bottom,
+                        );
+                    }
+
+                    // Spacing between neighbours collapses to the larger of
+                    // the two; spacing along the table's edges is dropped.
+                    if col > 0 {
+                        let g = &mut column_gaps[col - 1];
+                        *g = f32::max(*g, rc.spacing.left);
+                    }
+                    if col + colspan < total_cols {
+                        let g = &mut column_gaps[col + colspan - 1];
+                        *g = f32::max(*g, rc.spacing.right);
+                    }
+                    if row > 0 {
+                        let g = &mut row_gaps[row - 1];
+                        *g = f32::max(*g, rc.spacing.top);
+                    }
+                    if row + rowspan < total_rows {
+                        let g = &mut row_gaps[row + rowspan - 1];
+                        *g = f32::max(*g, rc.spacing.bottom);
                     }
                 }
                 // flop to a new row
                 LayoutOp::Row => {
+                    occupancy.next_row();
                     row += 1;
-                    col = 0;
                 }
             }
         }
 
-        let mut slack: Vec<f32> = Vec::new();
-
-        // Calculate error along width distribution
-        let mut error = width;
-        for c in &col_sizes {
-            // Error is what remains once we have given each column its preferred size.
-            error -= c.preferred.width;
+        // Rows grow if lining up their baselines needs more room.
+        for (r, size) in row_sizes.iter_mut().enumerate() {
+            let aligned = above_baseline[r] + below_baseline[r];
+            size.preferred.height = f32::max(size.preferred.height, aligned);
+            row_compressed[r] = f32::max(row_compressed[r], aligned);
         }
 
-        if error > 0.0 {
-            // Extra space; relax the layout if we need to
-            // Figure out how many columns are expanding horizontally.
-            let expansions = has_xexpand.iter().filter(|x| **x).count();
-            if expansions > 0 {
-                let amount = error / expansions as f32;
-                for (i, e) in has_xexpand.iter().enumerate() {
-                    if *e {
-                        col_sizes[i].preferred.width += amount;
-                    }
+        // Tracks holding nothing but collapsed cells vanish, gaps and all.
+        let col_vanished: Vec<bool> = (0..total_cols)
+            .map(|c| col_occupied[c] && !col_shown[c])
+            .collect();
+        let row_vanished: Vec<bool> = (0..total_rows)
+            .map(|r| row_occupied[r] && !row_shown[r])
+            .collect();
+        TableLayout::collapse_gaps(&mut column_gaps, &col_vanished);
+        TableLayout::collapse_gaps(&mut row_gaps, &row_vanished);
+
+        let columns = (0..total_cols)
+            .map(|c| {
+                if col_vanished[c] {
+                    return Track::new(0.0, 0.0, 0.0, 0.0, 0.0, 0, 0.0);
+                }
+                let size = &col_sizes[c];
+                Track::new(
+                    size.minimum.width,
+                    col_compressed[c],
+                    size.preferred.width,
+                    size.maximum.width,
+                    col_grow[c],
+                    col_priority[c],
+                    col_fraction[c],
+                )
+            })
+            .collect();
+        let rows = (0..total_rows)
+            .map(|r| {
+                if row_vanished[r] {
+                    return Track::new(0.0, 0.0, 0.0, 0.0, 0.0, 0, 0.0);
                 }
+                let size = &row_sizes[r];
+                Track::new(
+                    size.minimum.height,
+                    row_compressed[r],
+                    size.preferred.height,
+                    size.maximum.height,
+                    row_grow[r],
+                    row_priority[r],
+                    row_fraction[r],
+                )
+            })
+            .collect();
+
+        Plan {
+            cells,
+            resolved,
+            columns,
+            rows,
+            row_baselines: above_baseline,
+            column_gaps,
+            row_gaps,
+        }
+    }
+
+    /// Removes the gaps around tracks which have vanished. Visible
+    /// tracks on either side of them are kept apart by the largest of
+    /// the gaps in between, just as if they were neighbours.
+    fn collapse_gaps(gaps: &mut [f32], vanished: &[bool]) {
+        let mut pending: f32 = 0.0;
+        let mut seen = false;
+        for (i, gone) in vanished.iter().enumerate() {
+            if i > 0 {
+                pending = f32::max(pending, gaps[i - 1]);
+                gaps[i - 1] = 0.0;
             }
-        } else if error < 0.0 {
-            // Not enough space; tense up some more!
-            let error = -error;
-            // We need to find slack space for each column
-            let mut total_slack: f32 = 0.0;
-            slack.clear();
-            slack.resize(total_cols as usize, 0.0);
-            for (i, x) in col_sizes
-                .iter()
-                .map(|x| x.preferred.width - x.minimum.width)
-                .enumerate()
-            {
-                slack[i] = x;
-                total_slack += x;
+            if !gone {
+                if seen {
+                    gaps[i - 1] = pending;
+                }
+                seen = true;
+                pending = 0.0;
             }
+        }
+    }
 
-            // XXX if error > total_slack, it is impossible to solve this constraint
-            // spread error across slack space, proportionate to this areas slack participation
-            for mut s in &mut slack {
-                let norm = *s / total_slack;
-                let error_over_slack = error * norm;
-                *s -= error_over_slack
-            }
+    /// Works out the minimum, preferred and maximum size of the whole
+    /// table, including padding, spans and gaps. This is useful for
+    /// setting a window's minimum size, or placing the table inside
+    /// another container. No callbacks are run.
+    pub fn measure(&mut self) -> SizeGrouping {
+        let plan = self.plan();
+        let padding = self.table_padding();
+        let outer = Size {
+            width: padding.left + padding.right + plan.column_gaps.iter().sum::<f32>(),
+            height: padding.top + padding.bottom + plan.row_gaps.iter().sum::<f32>(),
+        };
+        let sum = |tracks: &[Track], f: fn(&Track) -> f32| tracks.iter().map(f).sum::<f32>();
 
-            // Spread error across slack space.
-            for (i, x) in slack.iter().enumerate() {
-                col_sizes[i].preferred.width = f32::max(col_sizes[i].minimum.width + *x, 0.0);
-            }
+        // Maximums are often unbounded, so adding them up can overflow.
+        SizeGrouping {
+            minimum: Size {
+                width: sum(&plan.columns, |t| t.minimum) + outer.width,
+                height: sum(&plan.rows, |t| t.minimum) + outer.height,
+            },
+            preferred: Size {
+                width: sum(&plan.columns, |t| t.size) + outer.width,
+                height: sum(&plan.rows, |t| t.size) + outer.height,
+            },
+            maximum: Size {
+                width: f32::min(sum(&plan.columns, |t| t.maximum) + outer.width, f32::MAX),
+                height: f32::min(sum(&plan.rows, |t| t.maximum) + outer.height, f32::MAX),
+            },
         }
+    }
 
-        // Calculate error along height distribution
-        let mut error = height;
-        for c in &row_sizes {
-            // Error is what remains once we have given each row its preferred size.
-            error -= c.preferred.height;
+    /// Returns the space left over for columns and rows once the
+    /// table's padding and the gaps between tracks have been taken out
+    /// of a `width` by `height` table.
+    fn track_space(&self, plan: &Plan, width: f32, height: f32) -> Size {
+        let padding = self.table_padding();
+        Size {
+            width: width - padding.left - padding.right - plan.column_gaps.iter().sum::<f32>(),
+            height: height - padding.top - padding.bottom - plan.row_gaps.iter().sum::<f32>(),
         }
+    }
 
-        if error > 0.0 {
-            // Extra space; relax the layout if we need to
-            // Figure out how many columns are expanding horizontally.
-            let expansions = has_yexpand.iter().filter(|y| **y).count();
-            if expansions > 0 {
-                let amount = error / expansions as f32;
-                for (i, e) in has_yexpand.iter().enumerate() {
-                    if *e {
-                        row_sizes[i].preferred.height += amount;
+    /// Gives every column and row its final size within a `width` by
+    /// `height` table. Columns are sized first, so content whose height
+    /// depends on its width can be measured before rows are sized. If
+    /// the minimum sizes along an axis cannot fit, the layout is left
+    /// to overflow and the problem is reported alongside the plan.
+    fn solve(&mut self, width: f32, height: f32) -> (Plan, Option<LayoutError>) {
+        let mut plan = self.plan();
+        let mut error = None;
+
+        // Percentages can only be worked out once the space is known.
+        let space = self.track_space(&plan, width, height);
+        let percent = |r: Option<Relative>| matches!(r, Some(Relative::Percent(_)));
+        if plan
+            .cells
+            .iter()
+            .any(|cp| percent(cp.relative_width) || percent(cp.relative_height))
+        {
+            plan = self.plan_in(Some(&space));
+        }
+
+        let minimum = Track::minimum(&plan.columns);
+        if minimum > space.width {
+            error = Some(LayoutError::TooNarrow {
+                minimum: minimum + (width - space.width),
+                available: width,
+            });
+        }
+        Track::solve(&mut plan.columns, space.width);
+
+        // Widths are settled, so rows are sized again from the new heights.
+        if self.fit_heights(&mut plan) {
+            let Plan {
+                resolved, columns, ..
+            } = plan;
+            plan = Plan {
+                columns,
+                ..self.aggregate(resolved)
+            };
+        }
+
+        let minimum = Track::minimum(&plan.rows);
+        if minimum > space.height && error.is_none() {
+            error = Some(LayoutError::TooShort {
+                minimum: minimum + (height - space.height),
+                available: height,
+            });
+        }
+        Track::solve(&mut plan.rows, space.height);
+
+        (plan, error)
+    }
+
+    /// Asks content whose height depends on its width for its height at
+    /// the width its columns were given, and records the answer among
+    /// the plan's resolved cells. Returns whether any cell's height changed.
+    fn fit_heights(&mut self, plan: &mut Plan) -> bool {
+        let mut changed = false;
+        let mut occupancy = Occupancy::default();
+        let mut cell = 0;
+        for op in &mut self.opcodes {
+            match op {
+                LayoutOp::Cell(_, cp) => {
+                    let rc = &plan.cells[cell];
+                    let raw = &mut plan.resolved[cell];
+                    cell += 1;
+
+                    let col = match occupancy.place(cp.colspan, cp.rowspan) {
+                        Some(col) => col,
+                        None => continue,
+                    };
+                    if cp.content.is_none() || rc.visibility == Visibility::Collapsed {
+                        continue;
+                    }
+
+                    // Find the width the content will actually be given.
+                    let last = col + min(cp.colspan, plan.columns.len() - col) - 1;
+                    let area = Size {
+                        width: plan.columns[col..=last].iter().map(|t| t.size).sum::<f32>()
+                            + plan.column_gaps[col..last].iter().sum::<f32>(),
+                        height: 0.0,
+                    };
+                    let (_, _, width, _) = rc.size.box_fit(&area, rc);
+
+                    if let Some(content) = &mut cp.content {
+                        if let Some(height) = content.height_for_width(width) {
+                            raw.size.preferred.height = f32::min(
+                                f32::max(height, raw.size.minimum.height),
+                                raw.size.maximum.height,
+                            );
+                            changed = true;
+                        }
                     }
                 }
+                LayoutOp::Row => occupancy.next_row(),
             }
-        } else if error < 0.0 {
-            // Not enough space; tense up some more!
-            let error = -error;
-            // We need to find slack space for each row
-            let mut total_slack: f32 = 0.0;
-            slack.clear();
-            slack.resize(total_rows as usize, 0.0);
-            for (i, y) in row_sizes
-                .iter()
-                .map(|y| y.preferred.height - y.minimum.height)
-                .enumerate()
-            {
-                slack[i] = y;
-                total_slack += y;
-            }
+        }
+        changed
+    }
 
-            // XXX if error > total_slack, it is impossible to solve this constraint
-            // spread error across slack space, proportionate to this areas slack participation
-            for mut s in &mut slack {
-                let norm = *s / total_slack;
-                let error_over_slack = error * norm;
-                *s -= error_over_slack
-            }
+    /// Places every cell according to the final column and row sizes
+    /// of a plan, running callbacks and collecting the results. Cells
+    /// are placed within the table's padding inside of `area`.
+    fn place(&mut self, plan: &Plan, area: &Rectangle) -> LayoutResult {
+        let mut result = LayoutResult::default();
+        let mut col_widths: Vec<f32> = plan.columns.iter().map(|t| t.size).collect();
+        let mut row_heights: Vec<f32> = plan.rows.iter().map(|t| t.size).collect();
+
+        // Columns are laid out left to right and mirrored when placed, so
+        // a grid measured from the left here ends up measured from the side
+        // columns start from. Absolute grids are mirrored along with them.
+        let quantum = self.snap;
+        let (origin_x, origin_y) = match (self.snap_relative, self.direction) {
+            (true, _) => (area.left, area.top),
+            (false, Direction::LeftToRight) => (0.0, 0.0),
+            (false, Direction::RightToLeft) => (area.left + area.right, 0.0),
+        };
 
-            // Spread error across slack space.
-            for (i, y) in slack.iter().enumerate() {
-                row_sizes[i].preferred.height = f32::max(row_sizes[i].minimum.height + *y, 0.0);
-            }
+        // Find where each column and row begins.
+        let padding = self.table_padding();
+        let (start_pad, end_pad) = match self.direction {
+            Direction::LeftToRight => (padding.left, padding.right),
+            Direction::RightToLeft => (padding.right, padding.left),
+        };
+        let mut col_offsets: Vec<f32> = Vec::with_capacity(col_widths.len());
+        let mut x = area.left + start_pad;
+        for (i, w) in col_widths.iter().enumerate() {
+            col_offsets.push(x);
+            x += w + plan.column_gaps.get(i).unwrap_or(&0.0);
+        }
+
+        let mut row_offsets: Vec<f32> = Vec::with_capacity(row_heights.len());
+        let mut y = area.top + padding.top;
+        for (i, h) in row_heights.iter().enumerate() {
+            row_offsets.push(y);
+            y += h + plan.row_gaps.get(i).unwrap_or(&0.0);
+        }
+
+        if let Some(quantum) = quantum.filter(|q| *q > 0.0) {
+            TableLayout::snap_tracks(
+                &mut col_offsets,
+                &mut col_widths,
+                origin_x,
+                area.right - end_pad,
+                quantum,
+                self.snap_relative,
+            );
+            TableLayout::snap_tracks(
+                &mut row_offsets,
+                &mut row_heights,
+                origin_y,
+                area.bottom - padding.bottom,
+                quantum,
+                self.snap_relative,
+            );
         }
 
         // Preparations complete. Now we pass the news along to our client.
-        let mut x = 0.0;
-        let mut y = 0.0;
-        row = 0;
-        col = 0;
-        for mut op in &mut self.opcodes {
-            // NB can probably make this mutable, and update it only when the row changes
-            let height = row_sizes[row as usize].preferred.height;
+        let mut row: usize = 0;
+        let mut occupancy = Occupancy::default();
+        let mut cell = 0;
+        for op in &mut self.opcodes {
             match op {
                 // Something that needs to be placed.
-                LayoutOp::Cell(cp) => match &cp.colspan {
-                    0 => {} // Ignore this cell.
-                    _ => {
-                        let mut width: f32 = 0.0;
-                        for _i in 0..cp.colspan {
-                            width += col_sizes[col as usize].preferred.width;
-                            col += 1;
+                LayoutOp::Cell(id, cp) => {
+                    let rc = &plan.cells[cell];
+                    cell += 1;
+                    result.ids.push(*id);
+                    result.visibility.push(rc.visibility);
+
+                    match occupancy.place(cp.colspan, cp.rowspan) {
+                        // Ignore this cell, but keep its slot in the results.
+                        None => result.cells.push(Default::default()),
+                        // Collapsed cells have no space to be placed in.
+                        Some(_) if rc.visibility == Visibility::Collapsed => {
+                            result.cells.push(Default::default())
                         }
-                        let s = Size { width, height };
-                        let (bx, by, bw, bh) = cp.size.box_fit(&s, &cp);
+                        Some(col) => {
+                            // Spanning cells also take over the gaps they cross.
+                            // Spans reaching past the edges of the table stop there.
+                            let last_col = col + min(cp.colspan, col_widths.len() - col) - 1;
+                            let last_row = row + min(cp.rowspan, row_heights.len() - row) - 1;
+                            let width =
+                                col_offsets[last_col] + col_widths[last_col] - col_offsets[col];
+                            let height =
+                                row_offsets[last_row] + row_heights[last_row] - row_offsets[row];
+                            let x = col_offsets[col];
+                            let y = row_offsets[row];
+
+                            let s = Size { width, height };
+                            let (bx, mut by, bw, bh) = rc.size.box_fit(&s, rc);
+
+                            // Line up with the rest of the row's baseline, but
+                            // never past the edges of a row which has shrunk.
+                            if let Some(baseline) = rc.baseline {
+                                by = plan.row_baselines[row] - baseline;
+                                by = f32::max(f32::min(by, height - bh), 0.0);
+                            }
 
-                        // Run callback to impose layout.
-                        match &mut cp.callback {
-                            Some(cb) => {
-                                (*cb)(x + bx, y + by, bw, bh);
+                            let mut bx = x + bx;
+                            let mut by = y + by;
+                            let (mut bw, mut bh) = (bw, bh);
+
+                            // Cells inside of their tracks snap to the same
+                            // grid, without leaving the space they were given.
+                            if quantum.is_some() {
+                                let snap_x = |v: f32| {
+                                    let v = TableLayout::snap(v, origin_x, quantum);
+                                    f32::max(x, f32::min(v, x + width))
+                                };
+                                let snap_y = |v: f32| {
+                                    let v = TableLayout::snap(v, origin_y, quantum);
+                                    f32::max(y, f32::min(v, y + height))
+                                };
+                                let (left, top) = (snap_x(bx), snap_y(by));
+                                bw = snap_x(bx + bw) - left;
+                                bh = snap_y(by + bh) - top;
+                                bx = left;
+                                by = top;
+                            }
+
+                            if self.direction == Direction::RightToLeft {
+                                bx = area.left + area.right - (bx + bw);
                             }
-                            None => {}
-                        }
 
-                        x += width;
+                            // Only cells which are shown are told where they went.
+                            if rc.visibility == Visibility::Visible {
+                                // Run callback to impose layout.
+                                if let Some(cb) = &mut cp.callback {
+                                    (*cb)(bx, by, bw, bh);
+                                }
+
+                                // Nested tables get laid out in the space they were given.
+                                if let Some(table) = &mut cp.table {
+                                    table.impose_in(Rectangle::from_box(bx, by, bw, bh));
+                                }
+                            }
+
+                            result.cells.push(Rectangle::from_box(bx, by, bw, bh));
+                        }
                     }
-                },
+                }
                 // Increment to next row; reset placement cursors.
                 LayoutOp::Row => {
-                    x = 0.0;
-                    y += height;
+                    occupancy.next_row();
                     row += 1;
-                    col = 0;
                 }
             }
         }
+
+        result.column_widths = col_widths;
+        result.row_heights = row_heights;
+        result
+    }
+
+    /// Decides the position of every cell within a table of the given
+    /// `width` and `height`. Each cell's callback is informed of its
+    /// placement, and the same placements are returned as a
+    /// `LayoutResult` for callers who would rather inspect plain data.
+    ///
+    /// If the minimum sizes of the columns or rows add up to more than
+    /// the space available, every column or row along that axis is
+    /// clamped to its minimum size and the layout overflows the table
+    /// to the right or bottom. Use `try_impose` to detect this instead.
+    pub fn impose(&mut self, width: f32, height: f32) -> LayoutResult {
+        self.impose_in(Rectangle::from_box(0.0, 0.0, width, height))
+    }
+
+    /// Like `impose`, but lays the table out within `area`. Callbacks
+    /// and results are offset by the area's top left corner.
+    pub fn impose_in(&mut self, area: Rectangle) -> LayoutResult {
+        let (plan, _) = self.solve(area.width(), area.height());
+        self.place(&plan, &area)
+    }
+
+    /// Like `impose`, but fails without running any callbacks if the
+    /// minimum sizes of the columns or rows cannot fit in the given
+    /// `width` and `height`.
+    pub fn try_impose(&mut self, width: f32, height: f32) -> Result<LayoutResult, LayoutError> {
+        self.try_impose_in(Rectangle::from_box(0.0, 0.0, width, height))
+    }
+
+    /// Like `impose_in`, but fails without running any callbacks if the
+    /// minimum sizes of the columns or rows cannot fit within `area`.
+    pub fn try_impose_in(&mut self, area: Rectangle) -> Result<LayoutResult, LayoutError> {
+        match self.solve(area.width(), area.height()) {
+            (_, Some(error)) => Err(error),
+            (plan, None) => Ok(self.place(&plan, &area)),
+        }
     }
 }
 
+pub mod mig;
+
 #[cfg(test)]
 mod test;
@@ -0,0 +1,672 @@
+//! Constraint strings in the style of MIG Layout.
+//!
+//! Cells are described by comma separated constraints, such as
+//! `"pushx, growx, span 2, gap 10, align right, wrap"`, and columns or
+//! rows by a list of bracketed constraints, such as `"[grow, fill][100!]"`.
+//!
+//! | Cell constraint | Effect |
+//! |---|---|
+//! | `wrap` | starts a new row after the cell |
+//! | `newline` | starts a new row before the cell |
+//! | `grow`, `growx`, `growy` | fills the space the cell is given |
+//! | `push [weight]`, `pushx`, `pushy` | expands the column or row |
+//! | `span cols [rows]`, `spanx cols`, `spany rows` | spans one or more columns and rows |
+//! | `gap left [right] [top] [bottom]`, `gapx`, `gapy`, `gapleft`, ... | spacing around the cell |
+//! | `pad top [left] [bottom] [right]` | padding around the cell |
+//! | `align x [y]`, `alignx`, `aligny` | anchors the cell, once along each axis |
+//! | `width size`, `height size`, `wmin`, `wmax`, `hmin`, `hmax` | sizes the cell |
+//! | `shrinkprio priority` | sets the shrink priority; higher priorities shrink first |
+//!
+//! Columns and rows have keywords of their own, which apply along the
+//! track's axis. The cell keywords above are not allowed there.
+//!
+//! | Column or row constraint | Effect |
+//! |---|---|
+//! | `grow [weight]` | expands the column or row |
+//! | `fill` | fills the column or row with its cells |
+//! | `shrinkprio priority` | sets the shrink priority; higher priorities shrink first |
+//! | `align value` | anchors the cells of the column or row |
+//! | `size` | sizes the column or row, such as `100!` or `50:100` |
+//!
+//! Sizes are given as `min:preferred:max`, where any part may be left
+//! out, or as `size!` to fix all three. Lengths may end in `px`, and a
+//! preferred size may be a percentage of the table, such as `25%`.
+//!
+//! MIG's gaps between bracketed columns or rows, such as `"[]10[]"`, are
+//! not supported, since tables only have a single gap between all of
+//! their columns and another between all of their rows. They are
+//! reported as invalid values; use `TableLayout::with_gaps`, or `gap`
+//! on the cells themselves, instead. MIG's `shrink` weights are not
+//! supported either, since columns and rows give up space in proportion
+//! to how far they may shrink; `shrinkprio` orders them instead.
+
+use std::error;
+use std::fmt;
+use std::mem;
+use {CellId, CellProperties, Relative, TableLayout};
+
+/// A cell described by a constraint string, along with the row breaks
+/// around it.
+pub struct Component {
+    pub properties: CellProperties,
+    /// Whether a new row is started before the cell.
+    pub newline: bool,
+    /// Whether a new row is started after the cell.
+    pub wrap: bool,
+}
+
+/// Reasons a constraint string could not be parsed. Positions are byte
+/// offsets into the string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The constraint is not known, or not allowed in this place.
+    UnknownConstraint { position: usize, constraint: String },
+    /// A value could not be understood.
+    InvalidValue { position: usize, value: String },
+    /// The constraint needs a value which was not given.
+    MissingValue { position: usize, constraint: String },
+    /// The constraint was given more values than it takes.
+    UnexpectedValue { position: usize, value: String },
+    /// A bracket was opened but never closed.
+    UnclosedBracket { position: usize },
+}
+
+impl ParseError {
+    /// Returns the byte offset into the constraint string where the
+    /// problem was found.
+    pub fn position(&self) -> usize {
+        match self {
+            ParseError::UnknownConstraint { position, .. }
+            | ParseError::InvalidValue { position, .. }
+            | ParseError::MissingValue { position, .. }
+            | ParseError::UnexpectedValue { position, .. }
+            | ParseError::UnclosedBracket { position } => *position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnknownConstraint {
+                position,
+                constraint,
+            } => write!(f, "unknown constraint `{}` at {}", constraint, position),
+            ParseError::InvalidValue { position, value } => {
+                write!(f, "invalid value `{}` at {}", value, position)
+            }
+            ParseError::MissingValue {
+                position,
+                constraint,
+            } => write!(f, "`{}` at {} is missing a value", constraint, position),
+            ParseError::UnexpectedValue { position, value } => {
+                write!(f, "unexpected value `{}` at {}", value, position)
+            }
+            ParseError::UnclosedBracket { position } => {
+                write!(f, "bracket at {} is never closed", position)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// A single word of a constraint, and where it was found.
+struct Word<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Word<'a> {
+    /// Returns the part of this word between bytes `start` and `end`.
+    fn slice(&self, start: usize, end: usize) -> Word<'a> {
+        Word {
+            text: &self.text[start..end],
+            position: self.position + start,
+        }
+    }
+
+    fn invalid(&self) -> ParseError {
+        ParseError::InvalidValue {
+            position: self.position,
+            value: self.text.to_string(),
+        }
+    }
+
+    /// Reads a length in pixels, such as `10` or `10px`.
+    fn length(&self) -> Result<f32, ParseError> {
+        let text = self.text.trim_end_matches("px");
+        match text.parse::<f32>() {
+            Ok(n) if n.is_finite() && n >= 0.0 => Ok(n),
+            _ => Err(self.invalid()),
+        }
+    }
+
+    /// Reads a MIG shrink priority. MIG shrinks higher priorities first
+    /// and defaults to 100, while tables shrink lower priorities first
+    /// and default to zero, so the scale is turned around.
+    fn shrink_priority(&self) -> Result<i32, ParseError> {
+        self.text
+            .parse::<i32>()
+            .ok()
+            .and_then(|priority| 100i32.checked_sub(priority))
+            .ok_or_else(|| self.invalid())
+    }
+
+    /// Reads a whole number of at least one, such as a span.
+    fn count(&self) -> Result<usize, ParseError> {
+        match self.text.parse() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(self.invalid()),
+        }
+    }
+}
+
+/// Splits `text` into words, remembering where each one started
+/// counting from `offset`.
+fn words(text: &str, offset: usize) -> Vec<Word<'_>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(Some((text.len(), ' '))) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                words.push(Word {
+                    text: &text[s..i],
+                    position: offset + s,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Anchors along one axis, as used by `align`.
+enum Align {
+    Horizontal(fn(CellProperties) -> CellProperties),
+    Vertical(fn(CellProperties) -> CellProperties),
+}
+
+fn align(word: &Word) -> Result<Align, ParseError> {
+    Ok(match word.text.to_lowercase().as_str() {
+        "left" => Align::Horizontal(CellProperties::anchor_left),
+        "right" => Align::Horizontal(CellProperties::anchor_right),
+        "center" => Align::Horizontal(CellProperties::anchor_horizontal_center),
+        "leading" => Align::Horizontal(CellProperties::anchor_start),
+        "trailing" => Align::Horizontal(CellProperties::anchor_end),
+        "top" => Align::Vertical(CellProperties::anchor_top),
+        "bottom" => Align::Vertical(CellProperties::anchor_bottom),
+        "baseline" => Align::Vertical(CellProperties::anchor_baseline),
+        _ => return Err(word.invalid()),
+    })
+}
+
+/// Returns the anchor `word` names along one axis. `center` is allowed
+/// along either axis.
+fn align_along(
+    word: &Word,
+    horizontal: bool,
+) -> Result<fn(CellProperties) -> CellProperties, ParseError> {
+    match align(word)? {
+        Align::Horizontal(f) if horizontal => Ok(f),
+        Align::Vertical(f) if !horizontal => Ok(f),
+        Align::Horizontal(_) if word.text.eq_ignore_ascii_case("center") => {
+            Ok(CellProperties::anchor_vertical_center)
+        }
+        _ => Err(word.invalid()),
+    }
+}
+
+/// Sizes along a single axis, read from `min:preferred:max` or `size!`.
+#[derive(Default)]
+struct Bound {
+    minimum: Option<f32>,
+    preferred: Option<f32>,
+    percent: Option<f32>,
+    maximum: Option<f32>,
+}
+
+fn bound(word: &Word) -> Result<Bound, ParseError> {
+    let mut bound = Bound::default();
+    if word.text.ends_with('!') {
+        let size = word.slice(0, word.text.len() - 1).length()?;
+        bound.minimum = Some(size);
+        bound.preferred = Some(size);
+        bound.maximum = Some(size);
+        return Ok(bound);
+    }
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in word.text.char_indices().chain(Some((word.text.len(), ':'))) {
+        if c == ':' {
+            parts.push(word.slice(start, i));
+            start = i + 1;
+        }
+    }
+
+    let length = |part: &Word| -> Result<Option<f32>, ParseError> {
+        if part.text.is_empty() {
+            Ok(None)
+        } else {
+            part.length().map(Some)
+        }
+    };
+    match parts.len() {
+        1 => {}
+        2 | 3 => bound.minimum = length(&parts[0])?,
+        _ => return Err(word.invalid()),
+    }
+    let preferred = &parts[if parts.len() == 1 { 0 } else { 1 }];
+    if preferred.text.ends_with('%') {
+        let percent = preferred.slice(0, preferred.text.len() - 1);
+        bound.percent = Some(percent.length()?);
+    } else {
+        bound.preferred = length(preferred)?;
+    }
+    if parts.len() == 3 {
+        bound.maximum = length(&parts[2])?;
+    }
+    Ok(bound)
+}
+
+/// Sets the sizes of `cp` along one axis, keeping any the bound leaves out.
+fn apply_bound(cp: &mut CellProperties, b: &Bound, horizontal: bool) {
+    let size = &mut cp.size;
+    if horizontal {
+        size.minimum.width = b.minimum.unwrap_or(size.minimum.width);
+        size.preferred.width = b.preferred.unwrap_or(size.preferred.width);
+        size.maximum.width = b.maximum.unwrap_or(size.maximum.width);
+        if let Some(percent) = b.percent {
+            cp.relative_width = Some(Relative::Percent(percent));
+        }
+    } else {
+        size.minimum.height = b.minimum.unwrap_or(size.minimum.height);
+        size.preferred.height = b.preferred.unwrap_or(size.preferred.height);
+        size.maximum.height = b.maximum.unwrap_or(size.maximum.height);
+        if let Some(percent) = b.percent {
+            cp.relative_height = Some(Relative::Percent(percent));
+        }
+    }
+}
+
+/// Checks `keyword` was given between `min` and `max` values.
+fn expect(keyword: &Word, values: &[Word], min: usize, max: usize) -> Result<(), ParseError> {
+    if values.len() < min {
+        Err(ParseError::MissingValue {
+            position: keyword.position,
+            constraint: keyword.text.to_string(),
+        })
+    } else if values.len() > max {
+        Err(ParseError::UnexpectedValue {
+            position: values[max].position,
+            value: values[max].text.to_string(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads an optional weight, which defaults to one.
+fn weight(values: &[Word]) -> Result<f32, ParseError> {
+    values.first().map_or(Ok(1.0), |w| w.length())
+}
+
+fn unknown(keyword: &Word) -> ParseError {
+    ParseError::UnknownConstraint {
+        position: keyword.position,
+        constraint: keyword.text.to_string(),
+    }
+}
+
+/// Applies a single cell constraint to `component`.
+fn apply(words: &[Word], component: &mut Component) -> Result<(), ParseError> {
+    let keyword = &words[0];
+    let values = &words[1..];
+    let name = keyword.text.to_lowercase();
+    let expect = |min: usize, max: usize| expect(keyword, values, min, max);
+
+    let cp = &mut component.properties;
+    // Runs one of the cell builders in place.
+    let build = |cp: &mut CellProperties, f: fn(CellProperties) -> CellProperties| {
+        *cp = f(mem::take(cp));
+    };
+    match name.as_str() {
+        "wrap" => {
+            expect(0, 0)?;
+            component.wrap = true;
+        }
+        "newline" => {
+            expect(0, 0)?;
+            component.newline = true;
+        }
+        "grow" => {
+            expect(0, 0)?;
+            build(cp, CellProperties::fill);
+        }
+        "growx" => {
+            expect(0, 0)?;
+            build(cp, CellProperties::fill_horizontal);
+        }
+        "growy" => {
+            expect(0, 0)?;
+            build(cp, CellProperties::fill_vertical);
+        }
+        "push" | "pushx" | "pushy" => {
+            expect(0, 1)?;
+            let weight = weight(values)?;
+            if name != "pushy" {
+                build(cp, CellProperties::expand_horizontal);
+                cp.grow_horizontal = weight;
+            }
+            if name != "pushx" {
+                build(cp, CellProperties::expand_vertical);
+                cp.grow_vertical = weight;
+            }
+        }
+        "span" => {
+            expect(1, 2)?;
+            cp.colspan = values[0].count()?;
+            if let Some(rows) = values.get(1) {
+                cp.rowspan = rows.count()?;
+            }
+        }
+        "spanx" => {
+            expect(1, 1)?;
+            cp.colspan = values[0].count()?;
+        }
+        "spany" => {
+            expect(1, 1)?;
+            cp.rowspan = values[0].count()?;
+        }
+        "gap" => {
+            expect(1, 4)?;
+            let mut sides = [
+                &mut cp.spacing.left,
+                &mut cp.spacing.right,
+                &mut cp.spacing.top,
+                &mut cp.spacing.bottom,
+            ];
+            for (side, value) in sides.iter_mut().zip(values) {
+                **side = value.length()?;
+            }
+        }
+        "gapx" => {
+            expect(1, 2)?;
+            cp.spacing.left = values[0].length()?;
+            cp.spacing.right = values.get(1).map_or(Ok(cp.spacing.left), |v| v.length())?;
+        }
+        "gapy" => {
+            expect(1, 2)?;
+            cp.spacing.top = values[0].length()?;
+            cp.spacing.bottom = values.get(1).map_or(Ok(cp.spacing.top), |v| v.length())?;
+        }
+        "gapleft" | "gapright" | "gaptop" | "gapbottom" => {
+            expect(1, 1)?;
+            let gap = values[0].length()?;
+            match name.as_str() {
+                "gapleft" => cp.spacing.left = gap,
+                "gapright" => cp.spacing.right = gap,
+                "gaptop" => cp.spacing.top = gap,
+                _ => cp.spacing.bottom = gap,
+            }
+        }
+        "pad" => {
+            expect(1, 4)?;
+            let mut sides = [
+                &mut cp.padding.top,
+                &mut cp.padding.left,
+                &mut cp.padding.bottom,
+                &mut cp.padding.right,
+            ];
+            for (side, value) in sides.iter_mut().zip(values) {
+                **side = value.length()?;
+            }
+        }
+        "align" | "al" => {
+            expect(1, 2)?;
+            // Each axis may only be given once; a second `center` is vertical.
+            let (mut horizontal, mut vertical) = (false, false);
+            for value in values {
+                let axis = match align(value)? {
+                    Align::Horizontal(_)
+                        if horizontal && value.text.eq_ignore_ascii_case("center") =>
+                    {
+                        Align::Vertical(CellProperties::anchor_vertical_center)
+                    }
+                    axis => axis,
+                };
+                let (f, seen) = match axis {
+                    Align::Horizontal(f) => (f, &mut horizontal),
+                    Align::Vertical(f) => (f, &mut vertical),
+                };
+                if *seen {
+                    return Err(value.invalid());
+                }
+                *seen = true;
+                build(cp, f);
+            }
+        }
+        "alignx" | "ax" | "aligny" | "ay" => {
+            expect(1, 1)?;
+            build(cp, align_along(&values[0], name.ends_with('x'))?);
+        }
+        "width" | "w" | "height" | "h" => {
+            expect(1, 1)?;
+            apply_bound(cp, &bound(&values[0])?, name.starts_with('w'));
+        }
+        "wmin" => {
+            expect(1, 1)?;
+            cp.size.minimum.width = values[0].length()?;
+        }
+        "wmax" => {
+            expect(1, 1)?;
+            cp.size.maximum.width = values[0].length()?;
+        }
+        "hmin" => {
+            expect(1, 1)?;
+            cp.size.minimum.height = values[0].length()?;
+        }
+        "hmax" => {
+            expect(1, 1)?;
+            cp.size.maximum.height = values[0].length()?;
+        }
+        "shrinkprio" | "shp" => {
+            expect(1, 1)?;
+            cp.shrink_priority = values[0].shrink_priority()?;
+        }
+        _ => return Err(unknown(keyword)),
+    }
+    Ok(())
+}
+
+/// Applies a single column or row constraint to `cp`. Sizes are given
+/// on their own, such as `100!`, and along the track's axis.
+fn apply_track(
+    words: &[Word],
+    cp: &mut CellProperties,
+    horizontal: bool,
+) -> Result<(), ParseError> {
+    let keyword = &words[0];
+    let values = &words[1..];
+    let name = keyword.text.to_lowercase();
+    let expect = |min: usize, max: usize| expect(keyword, values, min, max);
+
+    let build = |cp: &mut CellProperties, f: fn(CellProperties) -> CellProperties| {
+        *cp = f(mem::take(cp));
+    };
+    match name.as_str() {
+        "grow" => {
+            expect(0, 1)?;
+            let weight = weight(values)?;
+            if horizontal {
+                build(cp, CellProperties::expand_horizontal);
+                cp.grow_horizontal = weight;
+            } else {
+                build(cp, CellProperties::expand_vertical);
+                cp.grow_vertical = weight;
+            }
+        }
+        "fill" => {
+            expect(0, 0)?;
+            if horizontal {
+                build(cp, CellProperties::fill_horizontal);
+            } else {
+                build(cp, CellProperties::fill_vertical);
+            }
+        }
+        "shrinkprio" | "shp" => {
+            expect(1, 1)?;
+            cp.shrink_priority = values[0].shrink_priority()?;
+        }
+        "align" | "al" => {
+            expect(1, 1)?;
+            build(cp, align_along(&values[0], horizontal)?);
+        }
+        _ if name.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == ':') => {
+            expect(0, 0)?;
+            apply_bound(cp, &bound(keyword)?, horizontal);
+        }
+        _ => return Err(unknown(keyword)),
+    }
+    Ok(())
+}
+
+/// Runs `f` on the words of each comma separated constraint in `text`.
+/// `offset` is where `text` starts within the whole constraint string.
+fn apply_all<F>(text: &str, offset: usize, mut f: F) -> Result<(), ParseError>
+where
+    F: FnMut(&[Word]) -> Result<(), ParseError>,
+{
+    let mut start = 0;
+    for part in text.split(',') {
+        let words = words(part, offset + start);
+        if !words.is_empty() {
+            f(&words)?;
+        }
+        start += part.len() + 1;
+    }
+    Ok(())
+}
+
+/// Parses a cell's constraints, such as `"growx, span 2, wrap"`.
+pub fn parse(constraints: &str) -> Result<Component, ParseError> {
+    parse_with(constraints, CellProperties::new())
+}
+
+/// Parses a cell's constraints on top of existing `properties`, which
+/// keep anything the constraints do not mention.
+pub fn parse_with(constraints: &str, properties: CellProperties) -> Result<Component, ParseError> {
+    let mut component = Component {
+        properties,
+        newline: false,
+        wrap: false,
+    };
+    apply_all(constraints, 0, |words| apply(words, &mut component))?;
+    Ok(component)
+}
+
+/// Parses column constraints, such as `"[grow, fill][100!]"`, into one
+/// set of cell properties per column.
+pub fn parse_columns(constraints: &str) -> Result<Vec<CellProperties>, ParseError> {
+    parse_tracks(constraints, true)
+}
+
+/// Parses row constraints, such as `"[grow, fill][100!]"`, into one set
+/// of cell properties per row.
+pub fn parse_rows(constraints: &str) -> Result<Vec<CellProperties>, ParseError> {
+    parse_tracks(constraints, false)
+}
+
+/// Parses column or row constraints into one set of cell properties per
+/// track. Only the column and row keywords are allowed within the
+/// brackets, and gaps between the brackets are not supported.
+fn parse_tracks(constraints: &str, horizontal: bool) -> Result<Vec<CellProperties>, ParseError> {
+    let mut tracks = Vec::new();
+    let mut rest = constraints.char_indices();
+    while let Some((i, c)) = rest.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c != '[' {
+            let end = constraints[i..]
+                .find(|c: char| c.is_whitespace() || c == '[')
+                .map_or(constraints.len(), |e| i + e);
+            return Err(ParseError::InvalidValue {
+                position: i,
+                value: constraints[i..end].to_string(),
+            });
+        }
+
+        let close = match constraints[i..].find(']') {
+            Some(close) => i + close,
+            None => return Err(ParseError::UnclosedBracket { position: i }),
+        };
+        let mut cp = CellProperties::new();
+        apply_all(&constraints[i + 1..close], i + 1, |words| {
+            apply_track(words, &mut cp, horizontal)
+        })?;
+        tracks.push(cp);
+
+        // Carry on after the closing bracket.
+        for (j, _) in rest.by_ref() {
+            if j == close {
+                break;
+            }
+        }
+    }
+    Ok(tracks)
+}
+
+impl Component {
+    /// Adds this cell to a table, along with any row breaks around it.
+    /// Returns the cell's handle.
+    pub fn add_to(self, table: &mut TableLayout) -> CellId {
+        if self.newline {
+            table.with_row();
+        }
+        let id = table.with_cell(self.properties);
+        if self.wrap {
+            table.with_row();
+        }
+        id
+    }
+}
+
+impl TableLayout {
+    /// Adds a cell described by a constraint string, such as
+    /// `"growx, span 2, wrap"`, on top of the defaults for its row and
+    /// column. Nothing is added if the string cannot be parsed.
+    pub fn with_constraints(&mut self, constraints: &str) -> Result<CellId, ParseError> {
+        // Find any mistakes before touching the table.
+        let component = parse(constraints)?;
+        if component.newline {
+            self.with_row();
+        }
+        let mut component = parse_with(constraints, CellProperties::with_defaults(self))?;
+        component.newline = false;
+        Ok(component.add_to(self))
+    }
+
+    /// Sets the column defaults from column constraints, such as
+    /// `"[grow, fill][100!]"`. Cells made with `with_defaults` or
+    /// `with_constraints` pick them up.
+    pub fn with_column_constraints(&mut self, constraints: &str) -> Result<&mut Self, ParseError> {
+        for (column, cp) in parse_columns(constraints)?.into_iter().enumerate() {
+            self.column_defaults.insert(column, cp);
+        }
+        Ok(self)
+    }
+
+    /// Sets the row defaults from row constraints, such as
+    /// `"[grow][]"`. Cells made with `with_defaults` or
+    /// `with_constraints` pick them up.
+    pub fn with_row_constraints(&mut self, constraints: &str) -> Result<&mut Self, ParseError> {
+        for (row, cp) in parse_rows(constraints)?.into_iter().enumerate() {
+            self.row_defaults.insert(row, cp);
+        }
+        Ok(self)
+    }
+}
@@ -1,4 +1,6 @@
 use *;
+use std::cell::Cell;
+use std::rc::Rc;
 #[test]
 fn expanding_layout() {
     let mut engine = TableLayout::new();
@@ -145,6 +147,38 @@ fn centered_layout() {
     engine.impose(64.0, 64.0);
 }
 
+#[test]
+fn centered_on_one_axis() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .anchor_horizontal_center()
+            .expand()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    engine.with_row();
+    engine.with_cell(
+        CellProperties::new()
+            .anchor_vertical_center()
+            .expand()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+
+    let result = engine.impose(64.0, 128.0);
+    // centred across, but left at the top
+    assert_eq!(result.cells[0].left, 16.0);
+    assert_eq!(result.cells[0].top, 0.0);
+    // centred down, but left on the left
+    assert_eq!(result.cells[1].left, 0.0);
+    assert_eq!(result.cells[1].top, 80.0);
+}
+
 #[test]
 fn padded_big_cell() {
     let mut engine = TableLayout::new();
@@ -240,3 +274,1319 @@ fn padded_multilayout() {
     );
     engine.impose(320.0, 240.0);
 }
+
+#[test]
+fn layout_result() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .anchor_right()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    engine.with_cell(
+        CellProperties::new()
+            .expand_horizontal()
+            .fill_horizontal()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    engine.with_row();
+    engine.with_cell(
+        CellProperties::new()
+            .colspan(2)
+            .expand_vertical()
+            .fill()
+            .preferred_size(Size {
+                width: 64.0,
+                height: 16.0,
+            }),
+    );
+    let result = engine.impose(128.0, 64.0);
+
+    assert_eq!(result.column_widths, vec![32.0, 96.0]);
+    assert_eq!(result.row_heights, vec![32.0, 32.0]);
+    assert_eq!(result.cells.len(), 3);
+
+    assert_eq!(result.cells[0].left, 0.0);
+    assert_eq!(result.cells[0].top, 0.0);
+    assert_eq!(result.cells[0].width(), 32.0);
+    assert_eq!(result.cells[0].height(), 32.0);
+
+    assert_eq!(result.cells[1].left, 32.0);
+    assert_eq!(result.cells[1].top, 0.0);
+    assert_eq!(result.cells[1].width(), 96.0);
+    assert_eq!(result.cells[1].height(), 32.0);
+
+    assert_eq!(result.cells[2].left, 0.0);
+    assert_eq!(result.cells[2].top, 32.0);
+    assert_eq!(result.cells[2].width(), 128.0);
+    assert_eq!(result.cells[2].height(), 32.0);
+
+    // results are plain data, so they can be compared and stored
+    assert_eq!(result.cells[1], Rectangle::from_box(32.0, 0.0, 96.0, 32.0));
+    assert_eq!(engine.impose(128.0, 64.0), result);
+    assert_ne!(engine.impose(128.0, 80.0), result);
+}
+
+#[test]
+fn row_spanning_layout() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .rowspan(2)
+            .fill_vertical()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 48.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 64.0,
+        height: 16.0,
+    }));
+    engine.with_row();
+    // the first column is still covered, so this lands in the second
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 64.0,
+        height: 16.0,
+    }));
+    engine.with_row();
+    engine.with_cell(
+        CellProperties::new()
+            .colspan(2)
+            .fill_horizontal()
+            .preferred_size(Size {
+                width: 64.0,
+                height: 16.0,
+            }),
+    );
+
+    assert_eq!(engine.get_rows_cols(), (3, 2));
+
+    let result = engine.impose(96.0, 64.0);
+    assert_eq!(result.column_widths, vec![32.0, 64.0]);
+    // the tall cell's height is spread over the two rows it spans
+    assert_eq!(result.row_heights, vec![24.0, 24.0, 16.0]);
+
+    assert_eq!(result.cells[0].left, 0.0);
+    assert_eq!(result.cells[0].top, 0.0);
+    assert_eq!(result.cells[0].height(), 48.0);
+
+    assert_eq!(result.cells[1].left, 32.0);
+    assert_eq!(result.cells[1].top, 0.0);
+
+    assert_eq!(result.cells[2].left, 32.0);
+    assert_eq!(result.cells[2].top, 24.0);
+
+    assert_eq!(result.cells[3].left, 0.0);
+    assert_eq!(result.cells[3].top, 48.0);
+    assert_eq!(result.cells[3].width(), 96.0);
+}
+
+#[test]
+fn row_spanning_defaults() {
+    let mut engine = TableLayout::new();
+    engine.column_defaults.insert(0, CellProperties::new().padding_all(1.0));
+    engine.column_defaults.insert(1, CellProperties::new().padding_all(2.0));
+
+    engine.with_cell(CellProperties::with_defaults(&engine).rowspan(2));
+    engine.with_cell(CellProperties::with_defaults(&engine));
+    engine.with_row();
+    // the first column is still covered, so the cursor skips past it
+    assert_eq!(engine.column, 1);
+    let id = engine.with_cell(CellProperties::with_defaults(&engine));
+    assert_eq!(engine.position(id), Some((1, 1)));
+    assert_eq!(engine.cell(id).unwrap().padding.left, 2.0);
+    assert_eq!(engine.column, 2);
+
+    engine.with_row();
+    assert_eq!(engine.column, 0);
+
+    // edits put the cursor back in the same place
+    engine.remove_cell(id);
+    engine.remove_row(2);
+    assert_eq!(engine.column, 1);
+}
+
+#[test]
+fn uniform_layout() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(CellProperties::new().uniform().preferred_size(Size {
+        width: 48.0,
+        height: 16.0,
+    }));
+    engine.with_cell(CellProperties::new().uniform().preferred_size(Size {
+        width: 32.0,
+        height: 24.0,
+    }));
+    engine.with_row();
+    // only shares the width of the button bar, not its height
+    engine.with_cell(
+        CellProperties::new()
+            .uniform_horizontal()
+            .preferred_size(Size {
+                width: 16.0,
+                height: 8.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 8.0,
+    }));
+    let result = engine.impose(96.0, 32.0);
+
+    assert_eq!(result.column_widths, vec![48.0, 48.0]);
+    assert_eq!(result.row_heights, vec![24.0, 8.0]);
+
+    assert_eq!(result.cells[0].width(), 48.0);
+    assert_eq!(result.cells[0].height(), 24.0);
+    assert_eq!(result.cells[1].width(), 48.0);
+    assert_eq!(result.cells[1].height(), 24.0);
+    assert_eq!(result.cells[2].width(), 48.0);
+    assert_eq!(result.cells[2].height(), 8.0);
+    assert_eq!(result.cells[3].width(), 16.0);
+}
+
+#[test]
+fn weighted_expansion() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .expand_horizontal()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    engine.with_cell(
+        CellProperties::new()
+            .grow_horizontal(3.0)
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 32.0,
+        height: 32.0,
+    }));
+    let result = engine.impose(256.0, 32.0);
+
+    // 160 spare pixels, split one part to three
+    assert_eq!(result.column_widths, vec![72.0, 152.0, 32.0]);
+}
+
+#[test]
+fn capped_expansion() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .expand()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }).maximum_size(Size {
+                width: 48.0,
+                height: 40.0,
+            }),
+    );
+    engine.with_cell(
+        CellProperties::new()
+            .expand_horizontal()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    engine.with_row();
+    engine.with_cell(
+        CellProperties::new()
+            .expand_vertical()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    let result = engine.impose(256.0, 128.0);
+
+    // the first column stops at its maximum; the rest goes to the second
+    assert_eq!(result.column_widths, vec![48.0, 208.0]);
+    // same for rows
+    assert_eq!(result.row_heights, vec![40.0, 88.0]);
+}
+
+#[test]
+fn unsatisfiable_layout() {
+    let mut engine = TableLayout::new();
+    for _i in 0..2 {
+        engine.with_cell(
+            CellProperties::new()
+                .callback(Box::new(|x, y, w, h| {
+                    println!("{} {} {} {}", x, y, w, h);
+                    assert!(!x.is_nan() && !y.is_nan() && !w.is_nan() && !h.is_nan());
+                })).minimum_size(Size {
+                    width: 32.0,
+                    height: 32.0,
+                }).preferred_size(Size {
+                    width: 32.0,
+                    height: 32.0,
+                }),
+        );
+    }
+
+    assert_eq!(
+        engine.try_impose(48.0, 32.0).err(),
+        Some(LayoutError::TooNarrow {
+            minimum: 64.0,
+            available: 48.0,
+        })
+    );
+    assert_eq!(
+        engine.try_impose(64.0, 16.0).err(),
+        Some(LayoutError::TooShort {
+            minimum: 32.0,
+            available: 16.0,
+        })
+    );
+
+    // infallible path clamps to the minimums and overflows
+    let result = engine.impose(48.0, 16.0);
+    assert_eq!(result.column_widths, vec![32.0, 32.0]);
+    assert_eq!(result.row_heights, vec![32.0]);
+    assert_eq!(result.cells[1].left, 32.0);
+}
+
+#[test]
+fn gapped_layout() {
+    let mut engine = TableLayout::new();
+    engine.with_gaps(8.0, 4.0);
+    engine.with_cell(
+        CellProperties::new()
+            .expand_horizontal()
+            .fill_horizontal()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 32.0,
+        height: 16.0,
+    }));
+    engine.with_row();
+    engine.with_cell(
+        CellProperties::new()
+            .colspan(2)
+            .fill_horizontal()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 16.0,
+            }),
+    );
+    let result = engine.impose(128.0, 36.0);
+
+    // the gap is taken out before spare space is handed out
+    assert_eq!(result.column_widths, vec![88.0, 32.0]);
+    assert_eq!(result.row_heights, vec![16.0, 16.0]);
+
+    assert_eq!(result.cells[0].left, 0.0);
+    assert_eq!(result.cells[0].width(), 88.0);
+    assert_eq!(result.cells[1].left, 96.0);
+    assert_eq!(result.cells[2].top, 20.0);
+    // spanning cells cover the gaps between their columns
+    assert_eq!(result.cells[2].width(), 128.0);
+}
+
+#[test]
+fn collapsed_spacing() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(CellProperties::new().spacing_all(4.0).preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    engine.with_cell(CellProperties::new().spacing_all(10.0).preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    engine.with_cell(CellProperties::new().spacing_left(2.0).preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    engine.with_row();
+    engine.with_cell(CellProperties::new().spacing_top(6.0).preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    let result = engine.impose(68.0, 42.0);
+
+    // neighbours share the larger spacing, and the edges get nothing
+    assert_eq!(result.cells[0].left, 0.0);
+    assert_eq!(result.cells[1].left, 26.0);
+    assert_eq!(result.cells[2].left, 52.0);
+    assert_eq!(result.cells[0].top, 0.0);
+    assert_eq!(result.cells[3].top, 26.0);
+}
+
+#[test]
+fn imposed_in_area() {
+    let mut engine = TableLayout::new();
+    engine.with_padding(&Rectangle {
+        top: 4.0,
+        left: 8.0,
+        bottom: 4.0,
+        right: 8.0,
+    });
+    engine.with_cell(
+        CellProperties::new()
+            .callback(Box::new(|x, y, w, h| {
+                println!("{} {} {} {}", x, y, w, h);
+                assert_eq!(x, 108.0);
+                assert_eq!(y, 54.0);
+                assert_eq!(w, 84.0);
+                assert_eq!(h, 42.0);
+            })).expand()
+            .fill()
+            .preferred_size(Size {
+                width: 32.0,
+                height: 32.0,
+            }),
+    );
+    let result = engine.impose_in(Rectangle::from_box(100.0, 50.0, 100.0, 50.0));
+
+    assert_eq!(result.column_widths, vec![84.0]);
+    assert_eq!(result.row_heights, vec![42.0]);
+    assert_eq!(result.cells[0].left, 108.0);
+    assert_eq!(result.cells[0].top, 54.0);
+}
+
+#[test]
+fn nested_table() {
+    let mut inner = TableLayout::new();
+    inner.with_cell(
+        CellProperties::new()
+            .callback(Box::new(|x, y, w, h| {
+                println!("{} {} {} {}", x, y, w, h);
+                assert_eq!(x, 32.0);
+                assert_eq!(y, 8.0);
+                assert_eq!(w, 16.0);
+                assert_eq!(h, 16.0);
+            })).preferred_size(Size {
+                width: 16.0,
+                height: 16.0,
+            }),
+    );
+    inner.with_row();
+    inner.with_cell(
+        CellProperties::new()
+            .callback(Box::new(|x, y, w, h| {
+                println!("{} {} {} {}", x, y, w, h);
+                assert_eq!(x, 32.0);
+                assert_eq!(y, 24.0);
+                assert_eq!(w, 24.0);
+                assert_eq!(h, 16.0);
+            })).preferred_size(Size {
+                width: 24.0,
+                height: 16.0,
+            }),
+    );
+
+    let mut engine = TableLayout::new();
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 32.0,
+        height: 16.0,
+    }));
+    engine.with_cell(
+        CellProperties::new()
+            .expand_vertical()
+            .anchor_center()
+            .table(inner),
+    );
+    let result = engine.impose(56.0, 48.0);
+
+    // the nested table asks for the size of its own contents
+    assert_eq!(result.column_widths, vec![32.0, 24.0]);
+    assert_eq!(result.row_heights, vec![48.0]);
+    assert_eq!(result.cells[1].height(), 32.0);
+}
+
+#[test]
+fn measured_table() {
+    let mut engine = TableLayout::new();
+    engine.with_gaps(4.0, 2.0);
+    engine.with_padding(&Rectangle {
+        top: 1.0,
+        left: 8.0,
+        bottom: 1.0,
+        right: 8.0,
+    });
+    engine.with_cell(
+        CellProperties::new()
+            .callback(Box::new(|_x, _y, _w, _h| panic!("measuring must not place cells")))
+            .padding_all(2.0)
+            .minimum_size(Size {
+                width: 16.0,
+                height: 8.0,
+            }).preferred_size(Size {
+                width: 32.0,
+                height: 16.0,
+            }).maximum_size(Size {
+                width: 64.0,
+                height: 32.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 32.0,
+        height: 16.0,
+    }));
+    engine.with_row();
+    engine.with_cell(CellProperties::new().colspan(2).preferred_size(Size {
+        width: 80.0,
+        height: 16.0,
+    }));
+
+    let size = engine.measure();
+    // columns: 40 + 40 from the spanning cell, plus a gap and padding
+    assert_eq!(size.preferred.width, 100.0);
+    // rows: 20 and 16, plus a gap and padding
+    assert_eq!(size.preferred.height, 40.0);
+    assert_eq!(size.minimum.width, 40.0);
+    assert_eq!(size.minimum.height, 16.0);
+    // unbounded maximums stay unbounded rather than overflowing
+    assert_eq!(size.maximum.width, f32::MAX);
+    assert_eq!(size.maximum.height, f32::MAX);
+}
+
+struct Label {
+    width: Rc<Cell<f32>>,
+    dirty: Rc<Cell<bool>>,
+    measurements: Rc<Cell<u32>>,
+}
+
+impl Measure for Label {
+    fn measure(&mut self) -> SizeGrouping {
+        self.dirty.set(false);
+        self.measurements.set(self.measurements.get() + 1);
+        SizeGrouping {
+            minimum: Size {
+                width: self.width.get(),
+                height: 16.0,
+            },
+            preferred: Size {
+                width: self.width.get(),
+                height: 16.0,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn changed(&self) -> bool {
+        self.dirty.get()
+    }
+}
+
+#[test]
+fn measured_content() {
+    let width = Rc::new(Cell::new(40.0));
+    let dirty = Rc::new(Cell::new(true));
+    let measurements = Rc::new(Cell::new(0));
+
+    let mut engine = TableLayout::new();
+    engine.with_cell(CellProperties::new().content(Box::new(Label {
+        width: width.clone(),
+        dirty: dirty.clone(),
+        measurements: measurements.clone(),
+    })));
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+
+    let result = engine.impose(56.0, 16.0);
+    assert_eq!(result.column_widths, vec![40.0, 16.0]);
+    assert_eq!(measurements.get(), 1);
+
+    // unchanged content is not measured again
+    engine.impose(56.0, 16.0);
+    assert_eq!(measurements.get(), 1);
+
+    width.set(24.0);
+    dirty.set(true);
+    let result = engine.impose(40.0, 16.0);
+    assert_eq!(result.column_widths, vec![24.0, 16.0]);
+    assert_eq!(measurements.get(), 2);
+}
+
+struct WrappedText {
+    length: f32,
+}
+
+impl Measure for WrappedText {
+    fn measure(&mut self) -> SizeGrouping {
+        SizeGrouping {
+            minimum: Size {
+                width: 16.0,
+                height: 16.0,
+            },
+            preferred: Size {
+                width: self.length,
+                height: 16.0,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn height_for_width(&mut self, width: f32) -> Option<f32> {
+        Some((self.length / width).ceil() * 16.0)
+    }
+}
+
+#[test]
+fn height_for_width() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .minimum_size(Size {
+                width: 32.0,
+                height: 16.0,
+            }).preferred_size(Size {
+                width: 32.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_cell(
+        CellProperties::new()
+            .fill_horizontal()
+            .content(Box::new(WrappedText { length: 100.0 })),
+    );
+    engine.with_row();
+    engine.with_cell(CellProperties::new().colspan(2).preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    let result = engine.impose(72.0, 128.0);
+
+    // 100 pixels of text squeezed into 40 wraps onto three lines
+    assert_eq!(result.column_widths, vec![32.0, 40.0]);
+    assert_eq!(result.row_heights, vec![48.0, 16.0]);
+    assert_eq!(result.cells[1].height(), 48.0);
+    assert_eq!(result.cells[2].top, 48.0);
+
+    // uniform cells are sized from the wrapped height, not the one before
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .uniform_vertical()
+            .content(Box::new(WrappedText { length: 100.0 }))
+            .preferred_size(Size {
+                width: 100.0,
+                height: 64.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().uniform_vertical().preferred_size(Size {
+        width: 16.0,
+        height: 32.0,
+    }));
+    let result = engine.impose(200.0, 200.0);
+    assert_eq!(result.row_heights, vec![32.0]);
+    assert_eq!(result.cells[0].height(), 32.0);
+    assert_eq!(result.cells[1].height(), 32.0);
+}
+
+#[test]
+fn baseline_alignment() {
+    let mut engine = TableLayout::new();
+    // label
+    engine.with_cell(
+        CellProperties::new()
+            .anchor_baseline()
+            .baseline(12.0)
+            .padding_bottom(10.0)
+            .preferred_size(Size {
+                width: 32.0,
+                height: 16.0,
+            }),
+    );
+    // text field
+    engine.with_cell(
+        CellProperties::new()
+            .anchor_baseline()
+            .baseline(16.0)
+            .preferred_size(Size {
+                width: 64.0,
+                height: 24.0,
+            }),
+    );
+    // button
+    engine.with_cell(
+        CellProperties::new()
+            .anchor_baseline()
+            .baseline(14.0)
+            .preferred_size(Size {
+                width: 32.0,
+                height: 20.0,
+            }),
+    );
+    let result = engine.impose(128.0, 64.0);
+
+    // 16 above the shared baseline, and the label's padding needs 14 below
+    assert_eq!(result.row_heights, vec![30.0]);
+    assert_eq!(result.cells[0].top, 4.0);
+    assert_eq!(result.cells[1].top, 0.0);
+    assert_eq!(result.cells[2].top, 2.0);
+    assert_eq!(result.cells[0].top + 12.0, 16.0);
+    assert_eq!(result.cells[2].top + 14.0, 16.0);
+
+    // a row squeezed below the aligned height still holds its cells
+    let result = engine.impose(128.0, 10.0);
+    assert_eq!(result.row_heights, vec![10.0]);
+    for cell in &result.cells {
+        assert!(cell.top >= 0.0 && cell.bottom <= 10.0);
+    }
+}
+
+#[test]
+fn right_to_left_layout() {
+    let mut engine = TableLayout::new();
+    engine.with_direction(Direction::RightToLeft);
+    engine.with_cell(CellProperties::new().anchor_start().preferred_size(Size {
+        width: 32.0,
+        height: 16.0,
+    }));
+    engine.with_cell(
+        CellProperties::new()
+            .expand_horizontal()
+            .anchor_end()
+            .padding_end(4.0)
+            .preferred_size(Size {
+                width: 16.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_row();
+    engine.with_cell(
+        CellProperties::new()
+            .colspan(2)
+            .anchor_right()
+            .preferred_size(Size {
+                width: 16.0,
+                height: 16.0,
+            }),
+    );
+    let result = engine.impose(100.0, 32.0);
+
+    // columns keep their logical order in the results
+    assert_eq!(result.column_widths, vec![32.0, 68.0]);
+    // the first column is placed on the right
+    assert_eq!(result.cells[0].left, 68.0);
+    // the end of a right to left row is on the left
+    assert_eq!(result.cells[1].left, 4.0);
+    // physical anchors keep their meaning
+    assert_eq!(result.cells[2].left, 84.0);
+}
+
+#[test]
+fn sacrificial_padding() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .padding_all(20.0)
+            .sacrificial_padding(&Default::default())
+            .preferred_size(Size {
+                width: 100.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_cell(
+        CellProperties::new()
+            .minimum_size(Size {
+                width: 50.0,
+                height: 16.0,
+            })
+            .preferred_size(Size {
+                width: 100.0,
+                height: 16.0,
+            }),
+    );
+    let result = engine.impose(210.0, 56.0);
+
+    // padding gives way before any content shrinks
+    assert_eq!(result.column_widths, vec![110.0, 100.0]);
+    assert_eq!(result.cells[0].left, 5.0);
+    assert_eq!(result.cells[0].width(), 100.0);
+    assert_eq!(result.cells[1].width(), 100.0);
+
+    // once the padding is gone, content shrinks as usual
+    let result = engine.impose(150.0, 56.0);
+    assert_eq!(result.cells[0].left, 0.0);
+    assert_eq!(result.column_widths[0] + result.column_widths[1], 150.0);
+    assert!(result.column_widths[1] < 100.0);
+}
+
+#[test]
+fn shrink_priority() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .shrink_priority(1)
+            .minimum_size(Size {
+                width: 50.0,
+                height: 16.0,
+            })
+            .preferred_size(Size {
+                width: 100.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_cell(
+        CellProperties::new()
+            .minimum_size(Size {
+                width: 50.0,
+                height: 16.0,
+            })
+            .preferred_size(Size {
+                width: 100.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_cell(
+        CellProperties::new()
+            .minimum_size(Size {
+                width: 10.0,
+                height: 16.0,
+            })
+            .preferred_size(Size {
+                width: 20.0,
+                height: 16.0,
+            }),
+    );
+
+    // the lower priority columns shrink together
+    let result = engine.impose(190.0, 16.0);
+    assert_eq!(result.column_widths, vec![100.0, 75.0, 15.0]);
+
+    // and collapse before the higher priority column gives anything up
+    let result = engine.impose(140.0, 16.0);
+    assert_eq!(result.column_widths, vec![80.0, 50.0, 10.0]);
+}
+
+#[test]
+fn relative_sizes() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(
+        CellProperties::new()
+            .relative_width(Relative::Percent(25.0))
+            .minimum_size(Size {
+                width: 200.0,
+                height: 16.0,
+            })
+            .preferred_size(Size {
+                width: 200.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().relative_width(Relative::Fraction(1.0)));
+    engine.with_cell(CellProperties::new().relative_width(Relative::Fraction(3.0)));
+
+    // a quarter of the table, with the rest shared out by fraction
+    let result = engine.impose(1000.0, 16.0);
+    assert_eq!(result.column_widths, vec![250.0, 187.5, 562.5]);
+
+    // but never less than the minimum
+    let result = engine.impose(600.0, 16.0);
+    assert_eq!(result.column_widths, vec![200.0, 100.0, 300.0]);
+}
+
+#[test]
+fn scaled_units() {
+    let mut engine = TableLayout::new();
+    engine.with_scale(2.0).with_em(8.0).with_unit(Unit::Dp);
+    engine.with_gaps(4.0, 0.0);
+    engine.with_cell(
+        CellProperties::new()
+            .unit(Unit::Dp)
+            .padding_all(2.0)
+            .preferred_size(Size {
+                width: 16.0,
+                height: 16.0,
+            }),
+    );
+    engine.with_cell(CellProperties::new().unit(Unit::Em).preferred_size(Size {
+        width: 2.0,
+        height: 1.0,
+    }));
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 10.0,
+        height: 10.0,
+    }));
+    let result = engine.impose(130.0, 40.0);
+
+    // everything comes back in physical pixels
+    assert_eq!(result.column_widths, vec![40.0, 32.0, 10.0]);
+    assert_eq!(result.cells[0].left, 4.0);
+    assert_eq!(result.cells[0].width(), 32.0);
+    assert_eq!(result.cells[1].left, 48.0);
+    assert_eq!(result.cells[1].height(), 16.0);
+    assert_eq!(result.cells[2].left, 88.0);
+
+    // nested tables follow the scale of the table they are in
+    let mut inner = TableLayout::new();
+    inner.with_cell(CellProperties::new().unit(Unit::Dp).preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    let mut outer = TableLayout::new();
+    outer.with_scale(2.0);
+    outer.with_cell(CellProperties::new().table(inner));
+    let result = outer.impose(64.0, 64.0);
+    assert_eq!(result.column_widths, vec![32.0]);
+    assert_eq!(result.row_heights, vec![32.0]);
+}
+
+#[test]
+fn snapped_layout() {
+    let mut engine = TableLayout::new();
+    engine.with_snapping(1.0);
+    for _ in 0..3 {
+        engine.with_cell(CellProperties::new().expand_horizontal().fill_horizontal());
+    }
+    let result = engine.impose(100.0, 16.0);
+
+    // boundaries are rounded, and the remainder goes to the middle column
+    assert_eq!(result.column_widths, vec![33.0, 34.0, 33.0]);
+    assert_eq!(result.cells[1].left, 33.0);
+    assert_eq!(result.cells[1].right, 67.0);
+    assert_eq!(result.cells[2].left, 67.0);
+    assert_eq!(result.cells[2].right, 100.0);
+
+    // the grid is absolute, so a table placed off it still lands on whole pixels
+    let result = engine.impose_in(Rectangle::from_box(10.5, 0.25, 100.0, 16.0));
+    let lefts: Vec<f32> = result.cells.iter().map(|c| c.left).collect();
+    assert_eq!(lefts, vec![11.0, 44.0, 77.0]);
+    assert_eq!(result.cells[2].right, 110.0);
+    assert_eq!(result.cells[0].top, 1.0);
+
+    // and mirrors along with right to left tables
+    engine.with_direction(Direction::RightToLeft);
+    let result = engine.impose_in(Rectangle::from_box(10.5, 0.25, 100.0, 16.0));
+    let lefts: Vec<f32> = result.cells.iter().map(|c| c.left).collect();
+    assert_eq!(lefts, vec![77.0, 44.0, 11.0]);
+    assert_eq!(result.cells[0].right, 110.0);
+
+    // a design grid can be measured from the table instead
+    let mut engine = TableLayout::new();
+    engine.with_relative_snapping(8.0);
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 30.0,
+        height: 16.0,
+    }));
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 50.0,
+        height: 16.0,
+    }));
+    let result = engine.impose_in(Rectangle::from_box(4.0, 0.0, 80.0, 16.0));
+    assert_eq!(result.column_widths, vec![32.0, 48.0]);
+    assert_eq!(result.cells[1].left, 36.0);
+
+    // the table's far edge stays put even when it is off the grid
+    let mut engine = TableLayout::new();
+    engine.with_relative_snapping(8.0);
+    for _ in 0..3 {
+        engine.with_cell(CellProperties::new().expand_horizontal().fill_horizontal());
+    }
+    let result = engine.impose(100.0, 16.0);
+    assert_eq!(result.column_widths, vec![32.0, 32.0, 36.0]);
+    assert_eq!(result.cells[2].left, 64.0);
+    assert_eq!(result.cells[2].right, 100.0);
+
+    // right to left tables are snapped from the right, where they start
+    engine.with_direction(Direction::RightToLeft);
+    let result = engine.impose(100.0, 16.0);
+    assert_eq!(result.column_widths, vec![32.0, 32.0, 36.0]);
+    assert_eq!(result.cells[0].left, 68.0);
+    assert_eq!(result.cells[0].right, 100.0);
+    assert_eq!(result.cells[1].left, 36.0);
+    assert_eq!(result.cells[2].left, 0.0);
+    assert_eq!(result.cells[2].right, 36.0);
+}
+
+#[test]
+fn large_table() {
+    let mut engine = TableLayout::new();
+    for _ in 0..1000 {
+        engine.with_cell(CellProperties::new().preferred_size(Size {
+            width: 16.0,
+            height: 16.0,
+        }));
+        engine.with_row();
+    }
+    assert_eq!(engine.row, 1000);
+    assert_eq!(engine.get_rows_cols(), (1000, 1));
+
+    let result = engine.impose(16.0, 16000.0);
+    assert_eq!(result.row_heights.len(), 1000);
+    assert_eq!(result.cells[999].top, 999.0 * 16.0);
+}
+
+#[test]
+fn wide_table() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(CellProperties::new().rowspan(300).preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    for _ in 0..300 {
+        engine.with_cell(CellProperties::new().preferred_size(Size {
+            width: 1.0,
+            height: 1.0,
+        }));
+    }
+    assert_eq!(engine.column, 301);
+    // The rowspan reaches past the only row there is, so it stops there.
+    assert_eq!(engine.get_rows_cols(), (1, 301));
+
+    let result = engine.impose(316.0, 300.0);
+    assert_eq!(result.cells[300].left, 315.0);
+}
+
+#[test]
+fn oversized_spans() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(CellProperties::new().colspan(usize::MAX).fill().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    // Nothing fits after a cell spanning every column there could be.
+    let dropped = engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    engine.with_row();
+    engine.with_cell(CellProperties::new().rowspan(usize::MAX).fill().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    engine.with_row();
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    assert_eq!(engine.position(dropped), None);
+    assert_eq!(engine.get_rows_cols(), (3, 2));
+
+    // Spans past the edges of the table cover what there is of it.
+    let result = engine.impose(32.0, 48.0);
+    assert_eq!(result.cells[0].right, 32.0);
+    assert_eq!(result.cells[2].bottom - result.cells[2].top, 32.0);
+    assert_eq!(result.cells[4].left, 16.0);
+    assert_eq!(result.cells[4].top, 32.0);
+}
+
+#[test]
+fn cell_handles() {
+    let mut engine = TableLayout::new();
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    let id = engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    engine.with_cell(CellProperties::new().preferred_size(Size {
+        width: 16.0,
+        height: 16.0,
+    }));
+    assert_eq!(engine.cell(id).unwrap().size.preferred.width, 16.0);
+
+    let placed = Rc::new(Cell::new(0.0));
+    let p = placed.clone();
+    {
+        let cp = engine.cell_mut(id).unwrap();
+        cp.size.preferred.width = 32.0;
+        cp.flags |= CellFlags::AnchorRight;
+        cp.padding.left = 4.0;
+        cp.callback = Some(Box::new(move |x, _, _, _| p.set(x)));
+    }
+    let result = engine.impose(100.0, 16.0);
+
+    // changes made through the handle are picked up by the next layout
+    assert_eq!(result.column_widths, vec![16.0, 36.0, 16.0]);
+    assert_eq!(placed.get(), 20.0);
+
+    // handles stay valid as more cells are added
+    engine.with_row();
+    let other = engine.with_cell(CellProperties::new());
+    assert!(other != id);
+    assert_eq!(engine.cell(id).unwrap().size.preferred.width, 32.0);
+}
+
+#[test]
+fn structural_editing() {
+    let mut engine = TableLayout::new();
+    let a = engine.with_cell(CellProperties::new().colspan(2));
+    engine.with_row();
+    let b = engine.with_cell(CellProperties::new());
+    let c = engine.with_cell(CellProperties::new());
+    engine.with_row();
+    let d = engine.with_cell(CellProperties::new());
+    engine
+        .row_defaults
+        .insert(2, CellProperties::new().padding_all(1.0));
+    assert_eq!(engine.get_rows_cols(), (3, 2));
+
+    // insert a row in the middle
+    assert!(engine.insert_row(1));
+    assert_eq!(engine.row, 3);
+    assert_eq!(engine.position(b), Some((2, 0)));
+    assert!(engine.row_defaults.contains_key(&3));
+    let e = engine.insert_cell(1, 0, CellProperties::new()).unwrap();
+    assert_eq!(engine.position(e), Some((1, 0)));
+    assert_eq!(engine.cell_at(0, 1), Some(a));
+
+    // insert a cell between two others
+    let f = engine.insert_cell(2, 1, CellProperties::new()).unwrap();
+    assert_eq!(engine.position(f), Some((2, 1)));
+    assert_eq!(engine.position(c), Some((2, 2)));
+
+    // delete a cell by handle and by position
+    assert!(engine.remove_cell(f).is_some());
+    assert!(engine.remove_cell(f).is_none());
+    assert!(engine.remove_cell_at(2, 1).is_some());
+    assert!(engine.cell(c).is_none());
+
+    // move the last row to the top, defaults and all
+    assert!(engine.move_row(3, 0));
+    assert_eq!(engine.position(d), Some((0, 0)));
+    assert_eq!(engine.position(a), Some((1, 0)));
+    assert!(engine.row_defaults.contains_key(&0));
+    assert!(!engine.row_defaults.contains_key(&3));
+
+    // moving past the last row leaves the table alone
+    let ops = engine.opcodes.len();
+    assert!(!engine.move_row(0, 4));
+    assert!(!engine.move_row(4, 0));
+    assert_eq!(engine.opcodes.len(), ops);
+    assert_eq!(engine.position(d), Some((0, 0)));
+
+    // remove a row; the ones after it move up
+    assert_eq!(engine.remove_row(1).map(|cells| cells.len()), Some(1));
+    assert_eq!(engine.position(e), Some((1, 0)));
+    assert_eq!(engine.position(b), Some((2, 0)));
+    assert_eq!(engine.row, 2);
+    assert_eq!(engine.column, 1);
+
+    // moved cells keep their handles
+    assert!(engine.move_cell(b, 0, 0));
+    assert_eq!(engine.position(b), Some((0, 0)));
+    assert_eq!(engine.position(d), Some((0, 1)));
+    assert_eq!(engine.get_rows_cols(), (2, 2));
+
+    // rows past the end of the table are turned down, leaving it alone
+    let ops = engine.opcodes.len();
+    assert!(!engine.insert_row(4));
+    assert!(engine.insert_cell(3, 0, CellProperties::new()).is_none());
+    assert!(!engine.move_cell(b, 3, 0));
+    assert_eq!(engine.opcodes.len(), ops);
+    assert_eq!(engine.position(b), Some((0, 0)));
+
+    // results follow the layout, so look moved cells up by handle
+    let result = engine.impose(64.0, 64.0);
+    assert_eq!(result.ids[0], b);
+    assert_eq!(result.cell(b).unwrap().left, result.cells[0].left);
+    assert!(result.cell(c).is_none());
+
+    // removing a row shortens the cells spanning across it
+    let mut engine = TableLayout::new();
+    let tall = engine.with_cell(CellProperties::new().rowspan(3));
+    engine.with_cell(CellProperties::new());
+    engine.with_row();
+    engine.with_cell(CellProperties::new());
+    engine.with_row();
+    engine.with_cell(CellProperties::new());
+    assert_eq!(engine.get_rows_cols(), (3, 2));
+    engine.remove_row(1);
+    assert_eq!(engine.get_rows_cols(), (2, 2));
+    assert_eq!(engine.cell(tall).unwrap().rowspan, 2);
+    assert_eq!(engine.cell_at(1, 0), Some(tall));
+
+    // inserting a row lengthens them, so cells below still flow around
+    let mut engine = TableLayout::new();
+    let tall = engine.with_cell(CellProperties::new().rowspan(2));
+    engine.with_cell(CellProperties::new());
+    engine.with_row();
+    let beside = engine.with_cell(CellProperties::new());
+    assert_eq!(engine.position(beside), Some((1, 1)));
+    assert!(engine.insert_row(1));
+    assert_eq!(engine.cell(tall).unwrap().rowspan, 3);
+    assert_eq!(engine.cell_at(1, 0), Some(tall));
+    assert_eq!(engine.position(beside), Some((2, 1)));
+}
+
+#[test]
+fn visibility_states() {
+    let size = Size {
+        width: 20.0,
+        height: 20.0,
+    };
+    let called = Rc::new(Cell::new(false));
+    let c = called.clone();
+
+    let mut engine = TableLayout::new();
+    engine.with_gaps(10.0, 10.0);
+    engine.with_cell(CellProperties::new().preferred_size(size.clone()));
+    engine.with_cell(CellProperties::new().collapsed().preferred_size(size.clone()));
+    engine.with_cell(CellProperties::new().preferred_size(size.clone()));
+    engine.with_row();
+    engine.with_cell(
+        CellProperties::new()
+            .hidden()
+            .preferred_size(size.clone())
+            .callback(Box::new(move |_, _, _, _| c.set(true))),
+    );
+    engine.with_cell(CellProperties::new().collapsed().preferred_size(size.clone()));
+    engine.with_cell(CellProperties::new().preferred_size(size.clone()));
+    engine.with_row();
+    engine.with_cell(CellProperties::new().collapsed().preferred_size(size.clone()));
+    let result = engine.impose(50.0, 50.0);
+
+    // the collapsed column and row vanish, along with one of their gaps
+    assert_eq!(result.column_widths, vec![20.0, 0.0, 20.0]);
+    assert_eq!(result.row_heights, vec![20.0, 20.0, 0.0]);
+    assert_eq!(result.cells[2].left, 30.0);
+    assert_eq!(result.cells[5].left, 30.0);
+
+    // hidden cells keep their space but are not shown
+    assert_eq!(result.visibility[3], Visibility::Hidden);
+    assert_eq!(result.cells[3].top, 30.0);
+    assert_eq!(result.cells[3].width(), 20.0);
+    assert!(!called.get());
+
+    // collapsed cells take no space at all
+    assert_eq!(result.visibility[1], Visibility::Collapsed);
+    assert_eq!(result.cells[1].width(), 0.0);
+}
+
+#[test]
+fn mig_constraints() {
+    let component =
+        mig::parse("pushx 2, growx, span 2 3, gap 10, pad 1 2 3 4, align right top, wrap").unwrap();
+    let cp = &component.properties;
+    assert!(cp.flags.contains(CellFlags::ExpandHorizontal | CellFlags::FillHorizontal));
+    assert!(!cp.flags.contains(CellFlags::ExpandVertical));
+    assert!(cp.flags.contains(CellFlags::AnchorRight | CellFlags::AnchorTop));
+    assert_eq!(cp.grow_horizontal, 2.0);
+    assert_eq!((cp.colspan, cp.rowspan), (2, 3));
+    assert_eq!(cp.spacing.left, 10.0);
+    assert_eq!((cp.padding.top, cp.padding.right), (1.0, 4.0));
+    assert!(component.wrap);
+    assert!(!component.newline);
+
+    let cp = mig::parse("width 10:25%:300px, height 20!").unwrap().properties;
+    assert_eq!(cp.size.minimum.width, 10.0);
+    assert_eq!(cp.relative_width, Some(Relative::Percent(25.0)));
+    assert_eq!(cp.size.maximum.width, 300.0);
+    assert_eq!(cp.size.minimum.height, 20.0);
+    assert_eq!(cp.size.maximum.height, 20.0);
+
+    // cells pick up column constraints, and rows break where asked
+    let mut engine = TableLayout::new();
+    engine.with_column_constraints("[grow, fill] [50:]").unwrap();
+    let a = engine.with_constraints("w 20").unwrap();
+    let b = engine.with_constraints("w 50, wrap").unwrap();
+    let flags = engine.cell(a).unwrap().flags;
+    assert!(flags.contains(CellFlags::ExpandHorizontal | CellFlags::FillHorizontal));
+    assert_eq!(engine.cell(b).unwrap().size.minimum.width, 50.0);
+
+    let result = engine.impose(200.0, 10.0);
+    assert_eq!(result.column_widths, vec![150.0, 50.0]);
+
+    let c = engine.with_constraints("span 2").unwrap();
+    assert_eq!(engine.position(c), Some((1, 0)));
+
+    // column constraints follow cells flowing around a rowspan
+    let mut engine = TableLayout::new();
+    engine.with_column_constraints("[][50:]").unwrap();
+    engine.with_constraints("spany 2").unwrap();
+    engine.with_constraints("w 10, wrap").unwrap();
+    let d = engine.with_constraints("w 10").unwrap();
+    assert_eq!(engine.position(d), Some((1, 1)));
+    assert_eq!(engine.cell(d).unwrap().size.minimum.width, 50.0);
+
+    // column and row keywords apply along their own axis
+    let columns = mig::parse_columns("[grow 2][100!, align right][shp 200]").unwrap();
+    assert!(columns[0].flags.contains(CellFlags::ExpandHorizontal));
+    assert!(!columns[0].flags.contains(CellFlags::FillHorizontal));
+    assert_eq!(columns[0].grow_horizontal, 2.0);
+    assert_eq!(columns[1].size.minimum.width, 100.0);
+    assert_eq!(columns[1].size.maximum.width, 100.0);
+    assert!(columns[1].flags.contains(CellFlags::AnchorRight));
+    assert_eq!(columns[2].shrink_priority, -100);
+    let rows = mig::parse_rows("[grow, fill][25%][align center]").unwrap();
+    assert!(rows[0].flags.contains(CellFlags::ExpandVertical | CellFlags::FillVertical));
+    assert_eq!(rows[1].relative_height, Some(Relative::Percent(25.0)));
+    assert!(rows[2].flags.contains(CellFlags::AnchorVerticalCenter));
+
+    // a growing column takes the extra space
+    let mut engine = TableLayout::new();
+    engine.with_column_constraints("[grow][]").unwrap();
+    engine.with_constraints("w 20").unwrap();
+    engine.with_constraints("w 20").unwrap();
+    assert_eq!(engine.impose(100.0, 10.0).column_widths, vec![80.0, 20.0]);
+
+    // higher MIG shrink priorities give way first, from a default of 100
+    assert_eq!(mig::parse("shp 100").unwrap().properties.shrink_priority, 0);
+    let mut engine = TableLayout::new();
+    let first = engine.with_constraints("w 0:50, shrinkprio 200").unwrap();
+    let other = engine.with_constraints("w 0:50").unwrap();
+    let result = engine.impose(60.0, 10.0);
+    assert_eq!(result.column_widths, vec![10.0, 50.0]);
+    assert_eq!(result.cell(first).unwrap().right, 10.0);
+    assert_eq!(result.cell(other).unwrap().left, 10.0);
+}
+
+#[test]
+fn mig_errors() {
+    let error = mig::parse("growx, sapn 2").err().unwrap();
+    assert_eq!(
+        error,
+        mig::ParseError::UnknownConstraint {
+            position: 7,
+            constraint: "sapn".to_string(),
+        }
+    );
+    assert_eq!(mig::parse("span x").err().unwrap().position(), 5);
+    assert_eq!(mig::parse("growx, span").err().unwrap().position(), 7);
+    assert_eq!(mig::parse("align right top left").err().unwrap().position(), 16);
+    assert_eq!(mig::parse("span 0").err().unwrap().position(), 5);
+    assert_eq!(mig::parse("spany 0").err().unwrap().position(), 6);
+    assert_eq!(mig::parse("align left right").err().unwrap().position(), 11);
+    assert_eq!(mig::parse("align top bottom").err().unwrap().position(), 10);
+    let cp = mig::parse("align center center").unwrap().properties;
+    assert!(cp.flags.contains(CellFlags::AnchorHorizontalCenter | CellFlags::AnchorVerticalCenter));
+    assert_eq!(mig::parse("width 1:2:3:4").err().unwrap().position(), 6);
+    assert_eq!(mig::parse_columns("[grow] x").err().unwrap().position(), 7);
+    assert_eq!(mig::parse_columns("[grow][wrap]").err().unwrap().position(), 7);
+    assert_eq!(mig::parse_columns("[grow][").err().unwrap().position(), 6);
+    // gaps between columns or rows are left to the table
+    assert_eq!(mig::parse_columns("[]10[]").err().unwrap().position(), 2);
+    // cell keywords are not allowed on columns and rows
+    assert_eq!(mig::parse_columns("[grow][pushx]").err().unwrap().position(), 7);
+    assert_eq!(mig::parse_columns("[growx]").err().unwrap().position(), 1);
+    assert_eq!(mig::parse_rows("[span 2]").err().unwrap().position(), 1);
+    assert_eq!(mig::parse_rows("[align left]").err().unwrap().position(), 7);
+    assert_eq!(mig::parse_columns("[100 200]").err().unwrap().position(), 5);
+
+    // nothing is added when a constraint is wrong
+    let mut engine = TableLayout::new();
+    assert!(engine.with_constraints("newline, bogus").is_err());
+    assert_eq!(engine.opcodes.len(), 0);
+}